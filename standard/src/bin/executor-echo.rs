@@ -0,0 +1,71 @@
+//! The echo server, on our own executor instead of tokio's
+
+use std::{env, error::Error};
+use futures::io::{AsyncReadExt, AsyncWriteExt};
+use standard::executor::{
+    net::{AsyncTcpListener, AsyncTcpStream},
+    Executor,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut addr = "127.0.0.1:6142".to_string();
+    let mut threads = 4;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => addr = args.next().ok_or("--listen needs an address")?,
+            "--threads" => {
+                let count = args.next().ok_or("--threads needs a number")?;
+                threads = count.parse()?;
+                if threads == 0 {
+                    return Err("--threads must be more than 0".into());
+                }
+            }
+            arg => return Err(format!("unexpected argument: {}", arg).into()),
+        }
+    }
+
+    let mut listener = AsyncTcpListener::bind(&addr)?;
+    println!("Listening on {}", listener.local_addr()?);
+
+    let executor = Executor::new(threads);
+    let spawner = executor.spawner();
+    executor.spawner().spawn(async move {
+        let mut id = 0u64;
+        loop {
+            match listener.accept().await {
+                Ok((socket, _)) => {
+                    id += 1;
+                    println!("Accepted connection {}", id);
+                    spawner.spawn(echo(socket));
+                }
+                Err(err) => eprintln!("Accept error {:?}", err),
+            }
+        }
+    });
+    executor.run();
+    Ok(())
+}
+
+async fn echo(mut socket: AsyncTcpStream) {
+    let mut buf = vec![0; 4096];
+    let mut amt = 0;
+    loop {
+        match socket.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => {
+                if let Err(err) = socket.write_all(&buf[..n]).await {
+                    eprintln!("IO error {:?}", err);
+                    return;
+                }
+                amt += n;
+            }
+            Err(err) => {
+                eprintln!("IO error {:?}", err);
+                return;
+            }
+        }
+    }
+    println!("wrote {} bytes", amt);
+}
@@ -0,0 +1,52 @@
+// Processes a stream of jobs on the executor, at most three at a time, and
+// reports the results in batches
+
+use std::time::{Duration, Instant};
+use standard::executor::{
+    stream::StreamAdapters,
+    sync::mpsc,
+    time, Executor,
+};
+
+/// How many jobs run at once
+const CONCURRENCY: usize = 3;
+
+fn main() {
+    let executor = Executor::new(4);
+    let spawner = executor.spawner();
+    let (jobs, queue) = mpsc::channel(CONCURRENCY);
+
+    // Queues a job every 50ms, waiting whenever the queue is full
+    spawner.spawn_named("producer", async move {
+        let mut ticks = time::interval(Duration::from_millis(50));
+        for job in 0..12u64 {
+            ticks.next().await;
+            println!("queued job {}", job);
+            jobs.send(job).await.unwrap();
+        }
+    });
+
+    let consumer = spawner.clone();
+    spawner.spawn_named("consumer", async move {
+        let started = Instant::now();
+        // Each job runs as a task of its own, so they can run in parallel.
+        // A job is only taken from the queue once there's room for it.
+        let mut results = queue
+            .map(|job| consumer.spawn(process(job)))
+            .buffer_unordered(CONCURRENCY)
+            .chunks_timeout(4, Duration::from_millis(300));
+        while let Some(batch) = results.next().await {
+            let batch: Vec<_> = batch.into_iter().map(Result::unwrap).collect();
+            println!("{:>5}ms: finished {:?}", started.elapsed().as_millis(), batch);
+        }
+    });
+
+    drop(spawner);
+    executor.run();
+}
+
+/// Takes a while, depending on the job, then returns its number
+async fn process(job: u64) -> u64 {
+    time::sleep(Duration::from_millis(100 + job * 37 % 5 * 60)).await;
+    job
+}
@@ -0,0 +1,79 @@
+//! Making tasks give their worker back now and then.
+//!
+//! A future only returns `Pending` when something it waits on isn't ready,
+//! so a task whose channels and locks always are could keep its worker
+//! forever. Each poll of a task gets a budget instead, and every resource
+//! that's ready spends some of it. Once it's spent, resources act as if
+//! they weren't ready and wake the task, so it goes to the back of the
+//! queue. Outside our executor there is no budget, so the primitives work
+//! the same as ever on other executors.
+
+use std::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+thread_local! {
+    static BUDGET: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Completes after letting the other ready tasks run once
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// The future returned by `yield_now`
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Runs `f`, which polls a task, with a budget of `polls` ready resources
+pub(crate) fn with_budget<R>(polls: u32, f: impl FnOnce() -> R) -> R {
+    let previous = BUDGET.with(|budget| budget.replace(Some(polls)));
+    let _restore = RestoreBudget { previous };
+    f()
+}
+
+/// Spends one poll of the current task's budget. Once it's spent, wakes
+/// the task to be polled again later and returns `Pending`.
+pub(crate) fn poll_proceed(cx: &mut Context) -> Poll<()> {
+    BUDGET.with(|budget| match budget.get() {
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(left) => {
+            budget.set(Some(left - 1));
+            Poll::Ready(())
+        }
+        None => Poll::Ready(()),
+    })
+}
+
+/// Puts back the budget of whatever was being polled before, even if the
+/// poll panics
+struct RestoreBudget {
+    previous: Option<u32>,
+}
+
+impl Drop for RestoreBudget {
+    fn drop(&mut self) {
+        let previous = self.previous;
+        BUDGET.with(|budget| budget.set(previous));
+    }
+}
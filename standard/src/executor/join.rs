@@ -0,0 +1,277 @@
+//! Getting a task's output back, or finding out why there isn't one
+
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll, Waker},
+};
+use futures::FutureExt;
+use super::coop;
+
+/// Resolves to the output of a spawned task. Dropping it detaches the
+/// task, which keeps running.
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<State<T>>>,
+    abort: AbortHandle,
+}
+
+/// Cancels a task without waiting for it
+#[derive(Clone)]
+pub struct AbortHandle {
+    state: Arc<AbortState>,
+}
+
+struct AbortState {
+    aborted: AtomicBool,
+    /// Weak, so that a task nothing else can wake is still dropped
+    task: Mutex<Option<Weak<dyn Schedule>>>,
+}
+
+/// Puts a task back on its executor's queue
+pub(crate) trait Schedule: Send + Sync {
+    fn schedule(self: Arc<Self>);
+}
+
+/// Why a task didn't produce its output
+pub struct JoinError {
+    repr: Repr,
+}
+
+enum Repr {
+    Panic(Box<dyn Any + Send + 'static>),
+    Cancelled,
+}
+
+enum State<T> {
+    Running(Option<Waker>),
+    Finished(Result<T, JoinError>),
+    /// The handle has returned the result
+    Taken,
+}
+
+pub(crate) type PanicHook = Arc<dyn Fn(&JoinError) + Send + Sync>;
+
+/// What an executor does when one of its tasks panics, besides telling the
+/// task's handle
+#[derive(Clone, Default)]
+pub(crate) struct PanicPolicy {
+    pub(crate) hook: Option<PanicHook>,
+    pub(crate) abort: bool,
+}
+
+/// Hands the result to the handle. If it's dropped without doing so, the
+/// task was dropped before it finished.
+struct Reporter<T> {
+    state: Option<Arc<Mutex<State<T>>>>,
+}
+
+/// Wraps `future` so that its output or panic goes to the returned handle
+///
+/// The wrapper is `Send` whenever `future` and its output are.
+pub(crate) fn join_pair<F>(
+    future: F,
+    panics: PanicPolicy,
+) -> (impl Future<Output = ()> + 'static, JoinHandle<F::Output>)
+where
+    F: Future + 'static,
+{
+    let state = Arc::new(Mutex::new(State::Running(None)));
+    let reporter = Reporter {
+        state: Some(state.clone()),
+    };
+    let abort = AbortHandle {
+        state: Arc::new(AbortState {
+            aborted: AtomicBool::new(false),
+            task: Mutex::new(None),
+        }),
+    };
+    let aborted = abort.clone();
+    let task = async move {
+        let mut future = Box::pin(AssertUnwindSafe(future).catch_unwind());
+        let result = futures::future::poll_fn(|cx| {
+            if aborted.is_aborted() {
+                return Poll::Ready(None);
+            }
+            future.as_mut().poll(cx).map(Some)
+        })
+        .await;
+        match result {
+            Some(Ok(output)) => reporter.report(Ok(output)),
+            Some(Err(payload)) => {
+                let err = JoinError::panic(payload);
+                panics.task_panicked(&err);
+                reporter.report(Err(err));
+            }
+            None => {
+                // Its destructors run before the handle hears it was
+                // cancelled, which dropping the reporter tells it
+                drop(future);
+                drop(reporter);
+            }
+        }
+    };
+    (task, JoinHandle { state, abort })
+}
+
+impl<T> JoinHandle<T> {
+    /// Cancels the task. Its future is dropped instead of being polled
+    /// again, and the handle resolves to a cancelled `JoinError`, unless
+    /// it has already finished.
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+
+    /// Can cancel the task after the handle has been awaited or dropped
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !matches!(*self.state.lock().unwrap(), State::Running(_))
+    }
+
+    /// Tells the handle which task to wake when it's aborted
+    pub(crate) fn attach<S: Schedule + 'static>(&self, task: &Arc<S>) {
+        let task: Weak<S> = Arc::downgrade(task);
+        *self.abort.state.task.lock().unwrap() = Some(task);
+    }
+}
+
+impl AbortHandle {
+    /// Like `JoinHandle::abort`
+    pub fn abort(&self) {
+        self.state.aborted.store(true, Ordering::SeqCst);
+        let task = self.state.task.lock().unwrap().as_ref().and_then(Weak::upgrade);
+        if let Some(task) = task {
+            task.schedule();
+        }
+    }
+
+    fn is_aborted(&self) -> bool {
+        self.state.aborted.load(Ordering::SeqCst)
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        futures::ready!(coop::poll_proceed(cx));
+        let mut state = self.state.lock().unwrap();
+        match std::mem::replace(&mut *state, State::Taken) {
+            State::Running(_) => {
+                *state = State::Running(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+            State::Finished(result) => Poll::Ready(result),
+            State::Taken => panic!("JoinHandle polled after it completed"),
+        }
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle").finish_non_exhaustive()
+    }
+}
+
+impl<T> Reporter<T> {
+    fn report(mut self, result: Result<T, JoinError>) {
+        if let Some(state) = self.state.take() {
+            finish(&state, result);
+        }
+    }
+}
+
+impl<T> Drop for Reporter<T> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            finish(&state, Err(JoinError { repr: Repr::Cancelled }));
+        }
+    }
+}
+
+fn finish<T>(state: &Mutex<State<T>>, result: Result<T, JoinError>) {
+    let previous = std::mem::replace(&mut *state.lock().unwrap(), State::Finished(result));
+    if let State::Running(Some(waker)) = previous {
+        waker.wake();
+    }
+}
+
+impl PanicPolicy {
+    pub(crate) fn task_panicked(&self, err: &JoinError) {
+        if let Some(ref hook) = self.hook {
+            hook(err);
+        }
+        if self.abort {
+            eprintln!("aborting, {}", err);
+            std::process::abort();
+        }
+    }
+}
+
+impl JoinError {
+    pub(crate) fn panic(payload: Box<dyn Any + Send + 'static>) -> JoinError {
+        JoinError {
+            repr: Repr::Panic(payload),
+        }
+    }
+
+    pub fn is_panic(&self) -> bool {
+        matches!(self.repr, Repr::Panic(_))
+    }
+
+    /// The task was aborted, or dropped before it finished
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.repr, Repr::Cancelled)
+    }
+
+    /// The value the task panicked with, to resume the panic with
+    /// `std::panic::resume_unwind`
+    pub fn into_panic(self) -> Box<dyn Any + Send + 'static> {
+        match self.repr {
+            Repr::Panic(payload) => payload,
+            Repr::Cancelled => panic!("the task was cancelled, it didn't panic"),
+        }
+    }
+
+    /// The panic message, if it was a string
+    fn message(&self) -> Option<&str> {
+        match self.repr {
+            Repr::Panic(ref payload) => payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str)),
+            Repr::Cancelled => None,
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.repr, self.message()) {
+            (Repr::Panic(_), Some(message)) => write!(f, "task panicked: {}", message),
+            (Repr::Panic(_), None) => write!(f, "task panicked"),
+            (Repr::Cancelled, _) => write!(f, "task was cancelled"),
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.repr {
+            Repr::Panic(_) => f.debug_tuple("Panic").field(&self.message()).finish(),
+            Repr::Cancelled => f.write_str("Cancelled"),
+        }
+    }
+}
+
+impl Error for JoinError {}
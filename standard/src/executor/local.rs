@@ -0,0 +1,252 @@
+//! Running futures on the current thread, for calling async code from
+//! ordinary synchronous code and for futures that aren't `Send`.
+//!
+//! The thread parks on the executor's own reactor, so that ready sockets
+//! wake it as well as wakers called from anywhere else.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    future::Future,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+use futures::{
+    future::LocalBoxFuture,
+    task::{waker, ArcWake},
+    FutureExt,
+};
+use super::{
+    join::{join_pair, PanicPolicy, Schedule},
+    reactor::Reactor,
+    time, JoinHandle,
+};
+
+/// Stands for the future passed to `block_on` in the ready queue
+const MAIN_TASK: usize = usize::MAX;
+
+/// Runs tasks on the thread that calls `block_on`. The tasks needn't be
+/// `Send`, so neither is the executor.
+pub struct LocalExecutor {
+    shared: Rc<LocalShared>,
+}
+
+/// Spawns new futures onto a `LocalExecutor`
+#[derive(Clone)]
+pub struct LocalSpawner {
+    shared: Rc<LocalShared>,
+}
+
+struct LocalShared {
+    tasks: RefCell<HashMap<usize, LocalTask>>,
+    next_id: Cell<usize>,
+    ready: Arc<ReadyQueue>,
+    timers: Arc<time::Driver>,
+    reactor: Arc<Reactor>,
+}
+
+struct LocalTask {
+    /// Taken out while it's being polled, so that it can spawn
+    future: Option<LocalBoxFuture<'static, ()>>,
+    waker: Waker,
+}
+
+/// The ids of the tasks that have been woken. Wakers can be called from any
+/// thread, so this is the only part of the executor they can reach.
+struct ReadyQueue {
+    ids: Mutex<VecDeque<usize>>,
+    reactor: Arc<Reactor>,
+}
+
+/// Wakes one task of a `LocalExecutor`
+struct TaskWaker {
+    id: usize,
+    ready: Arc<ReadyQueue>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<LocalShared>>> = const { RefCell::new(None) };
+}
+
+/// Runs `future` to completion on the current thread and returns its
+/// output. Tasks it spawns with `spawn_local` that are still running when
+/// it completes are cancelled.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    LocalExecutor::new().block_on(future)
+}
+
+/// Spawns `future` onto the `LocalExecutor` running the current task
+pub fn spawn_local<F: Future + 'static>(future: F) -> JoinHandle<F::Output> {
+    let shared = CURRENT.with(|current| current.borrow().clone());
+    let shared = shared.expect("spawn_local must be called from a LocalExecutor");
+    LocalSpawner { shared }.spawn(future)
+}
+
+impl LocalExecutor {
+    pub fn new() -> LocalExecutor {
+        let reactor = Arc::new(Reactor::new().expect("failed to create the reactor"));
+        LocalExecutor {
+            shared: Rc::new(LocalShared {
+                tasks: RefCell::new(HashMap::new()),
+                next_id: Cell::new(0),
+                ready: Arc::new(ReadyQueue {
+                    ids: Mutex::new(VecDeque::new()),
+                    reactor: reactor.clone(),
+                }),
+                timers: Arc::new(time::Driver::new()),
+                reactor,
+            }),
+        }
+    }
+
+    pub fn spawner(&self) -> LocalSpawner {
+        LocalSpawner {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Runs the spawned tasks until `future` completes, returning its
+    /// output. Tasks still running afterwards carry on at the next call.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let shared = &self.shared;
+        let _current = Enter::new(shared.clone());
+        let _timers = shared.timers.enter();
+        let _reactor = shared.reactor.enter();
+
+        futures::pin_mut!(future);
+        let main_waker = waker(Arc::new(TaskWaker {
+            id: MAIN_TASK,
+            ready: shared.ready.clone(),
+        }));
+        shared.ready.push(MAIN_TASK);
+
+        loop {
+            while let Some(id) = shared.ready.pop() {
+                if id != MAIN_TASK {
+                    shared.poll_task(id);
+                    continue;
+                }
+                let context = &mut Context::from_waker(&main_waker);
+                if let Poll::Ready(output) = future.as_mut().poll(context) {
+                    return output;
+                }
+            }
+
+            let (fired, next_timer) = shared.timers.fire_expired();
+            if fired == 0 && shared.ready.is_empty() {
+                let timeout = next_timer.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                shared.reactor.turn(timeout);
+            }
+        }
+    }
+}
+
+impl Default for LocalExecutor {
+    fn default() -> LocalExecutor {
+        LocalExecutor::new()
+    }
+}
+
+impl Drop for LocalExecutor {
+    fn drop(&mut self) {
+        // Cancels the tasks that haven't finished, and breaks the cycle
+        // from tasks holding a spawner back to the executor
+        let tasks = std::mem::take(&mut *self.shared.tasks.borrow_mut());
+        drop(tasks);
+    }
+}
+
+impl LocalSpawner {
+    /// Starts running `future` as a new task, the handle resolves to its
+    /// output. It's first polled when the executor next runs.
+    pub fn spawn<F: Future + 'static>(&self, future: F) -> JoinHandle<F::Output> {
+        let (future, handle) = join_pair(future, PanicPolicy::default());
+        let shared = &self.shared;
+        let id = shared.next_id.get();
+        shared.next_id.set(id + 1);
+
+        let task_waker = Arc::new(TaskWaker {
+            id,
+            ready: shared.ready.clone(),
+        });
+        handle.attach(&task_waker);
+        let waker = waker(task_waker);
+        let task = LocalTask {
+            future: Some(future.boxed_local()),
+            waker,
+        };
+        shared.tasks.borrow_mut().insert(id, task);
+        shared.ready.push(id);
+        handle
+    }
+}
+
+impl LocalShared {
+    fn poll_task(&self, id: usize) {
+        // Finished already, or being polled further up the stack
+        let (mut future, waker) = match self.tasks.borrow_mut().get_mut(&id) {
+            Some(task) => match task.future.take() {
+                Some(future) => (future, task.waker.clone()),
+                None => return,
+            },
+            None => return,
+        };
+
+        if future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending() {
+            if let Some(task) = self.tasks.borrow_mut().get_mut(&id) {
+                task.future = Some(future);
+            }
+        } else {
+            self.tasks.borrow_mut().remove(&id);
+        }
+    }
+}
+
+impl ReadyQueue {
+    fn push(&self, id: usize) {
+        self.ids.lock().unwrap().push_back(id);
+    }
+
+    fn pop(&self) -> Option<usize> {
+        self.ids.lock().unwrap().pop_front()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ids.lock().unwrap().is_empty()
+    }
+}
+
+impl Schedule for TaskWaker {
+    fn schedule(self: Arc<Self>) {
+        ArcWake::wake(self);
+    }
+}
+
+impl ArcWake for TaskWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.ready.push(arc_self.id);
+        // Unparks the executor's thread if it's waiting on the reactor
+        arc_self.ready.reactor.wake();
+    }
+}
+
+/// Makes a `LocalExecutor` the one `spawn_local` uses, until it's dropped
+struct Enter {
+    previous: Option<Rc<LocalShared>>,
+}
+
+impl Enter {
+    fn new(shared: Rc<LocalShared>) -> Enter {
+        let previous = CURRENT.with(|current| current.borrow_mut().replace(shared));
+        Enter { previous }
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
@@ -0,0 +1,732 @@
+//! A multi-threaded executor, grown from the example in:
+//! https://rust-lang.github.io/async-book/02_execution/04_executor.html
+//!
+//! Each worker thread has its own queue of ready tasks. A task woken from a
+//! worker goes onto that worker's queue, one woken from anywhere else goes
+//! onto the global injector queue. Workers with nothing to do take a batch
+//! from the injector, or steal from the other workers. Workers with still
+//! nothing to do wait for timers and sockets: one of them on the reactor,
+//! the rest on a condvar.
+//!
+//! There's a set of those queues for each priority, and workers look for
+//! higher priority tasks first. Now and then they look for the lowest
+//! priority first instead, so that it's slowed down rather than starved.
+
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    error::Error,
+    fmt,
+    future::Future,
+    iter,
+    panic::{self, AssertUnwindSafe, Location},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, PoisonError,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+use futures::{
+    future::BoxFuture,
+    task::{waker_ref, ArcWake},
+    FutureExt,
+};
+
+mod coop;
+mod join;
+pub mod local;
+mod monitor;
+pub mod net;
+mod reactor;
+mod scope;
+pub mod sim;
+pub mod stream;
+pub mod sync;
+pub mod time;
+
+use self::{
+    join::{join_pair, PanicPolicy, Schedule},
+    monitor::{Registry, TaskStats},
+    reactor::Reactor,
+};
+pub use self::{
+    coop::{yield_now, YieldNow},
+    join::{AbortHandle, JoinError, JoinHandle},
+    monitor::{Monitor, TaskInfo, TaskState},
+    scope::Scope,
+};
+
+/// How many tasks a busy worker polls between checking for due timers and
+/// ready sockets
+const CHECK_INTERVAL: u32 = 61;
+
+/// How many ready resources a task can poll before it has to yield, unless
+/// set with `Executor::poll_budget`
+const DEFAULT_POLL_BUDGET: u32 = 128;
+
+/// How many `Priority` levels there are
+const PRIORITIES: usize = 3;
+
+/// How long polling a task can take before the slow poll hook is called,
+/// unless set with `Executor::slow_poll_threshold`
+const SLOW_POLL_THRESHOLD: Duration = Duration::from_millis(100);
+
+type SlowPollHook = Arc<dyn Fn(&TaskInfo, Duration) + Send + Sync>;
+
+/// Runs tasks on a pool of worker threads
+pub struct Executor {
+    shared: Arc<Shared>,
+    /// Handed to the worker threads when they start, one queue for each
+    /// priority
+    queues: Vec<Vec<Worker<Arc<Task>>>>,
+}
+
+/// Spawns new futures onto an executor
+pub struct Spawner {
+    shared: Arc<Shared>,
+    /// Given to the tasks it spawns
+    priority: Priority,
+}
+
+/// How soon a ready task is polled, relative to the other ready tasks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+/// The error from `Spawner::try_spawn` when the executor already has as
+/// many tasks as it will admit. It gives back the future.
+pub struct SpawnError<F> {
+    future: F,
+}
+
+/// The state all the workers, spawners and tasks of an executor share
+struct Shared {
+    /// One for each priority
+    injectors: Vec<Injector<Arc<Task>>>,
+    /// One for each priority of each worker
+    stealers: Vec<Vec<Stealer<Arc<Task>>>>,
+    timers: Arc<time::Driver>,
+    reactor: Arc<Reactor>,
+    idle: Mutex<Idle>,
+    wakeup: Condvar,
+    /// Tasks whose future hasn't finished yet
+    tasks: AtomicUsize,
+    /// How many tasks `try_spawn` and `wait_and_spawn` let run at once
+    max_tasks: AtomicUsize,
+    /// Waiting in `wait_and_spawn` for a task to finish
+    admission_waiters: Mutex<Vec<Waker>>,
+    panics: Mutex<PanicPolicy>,
+    poll_budget: AtomicU32,
+    registry: Registry,
+    slow_poll_nanos: AtomicU64,
+    slow_poll_hook: Mutex<SlowPollHook>,
+    spawners: AtomicUsize,
+}
+
+struct Idle {
+    sleeping: usize,
+    /// Whether a worker is waiting on the reactor
+    polling: bool,
+    /// Set by `run`, until then running out of spawners doesn't shut down
+    /// as `Executor::spawner` can still make more
+    running: bool,
+    /// Set once there are no tasks left and no spawners to make more
+    shutdown: bool,
+}
+
+/// A future that can reschedule itself to be polled by an Executor
+struct Task {
+    /// In-progress future that should be pushed to completion.
+    future: Mutex<Option<BoxFuture<'static, ()>>>,
+
+    /// The executor to reschedule the task on
+    shared: Arc<Shared>,
+
+    priority: Priority,
+
+    /// Shared with the executor's `Monitor`
+    stats: Arc<TaskStats>,
+}
+
+/// What a worker thread knows about itself, so that wakers called on it
+/// can use its local queue
+struct WorkerContext {
+    shared: Arc<Shared>,
+    /// One for each priority
+    queues: Vec<Worker<Arc<Task>>>,
+    index: usize,
+    /// The task being polled, to tell when a task wakes itself
+    polling: Cell<Option<*const Task>>,
+}
+
+thread_local! {
+    static WORKER: RefCell<Option<WorkerContext>> = const { RefCell::new(None) };
+}
+
+impl Executor {
+    pub fn new(threads: usize) -> Executor {
+        assert!(threads > 0, "an executor needs at least one thread");
+        let queues: Vec<Vec<_>> = (0..threads)
+            .map(|_| (0..PRIORITIES).map(|_| Worker::new_fifo()).collect())
+            .collect();
+        let shared = Arc::new(Shared {
+            injectors: (0..PRIORITIES).map(|_| Injector::new()).collect(),
+            stealers: queues
+                .iter()
+                .map(|queues| queues.iter().map(Worker::stealer).collect())
+                .collect(),
+            timers: Arc::new(time::Driver::new()),
+            reactor: Arc::new(Reactor::new().expect("failed to create the reactor")),
+            idle: Mutex::new(Idle {
+                sleeping: 0,
+                polling: false,
+                running: false,
+                shutdown: false,
+            }),
+            wakeup: Condvar::new(),
+            tasks: AtomicUsize::new(0),
+            max_tasks: AtomicUsize::new(usize::MAX),
+            admission_waiters: Mutex::new(Vec::new()),
+            panics: Mutex::new(PanicPolicy::default()),
+            poll_budget: AtomicU32::new(DEFAULT_POLL_BUDGET),
+            registry: Registry::new(),
+            slow_poll_nanos: AtomicU64::new(SLOW_POLL_THRESHOLD.as_nanos() as u64),
+            slow_poll_hook: Mutex::new(Arc::new(|task, took| {
+                eprintln!("warning: a poll took {:?}, {}", took, task);
+            })),
+            spawners: AtomicUsize::new(0),
+        });
+        Executor { shared, queues }
+    }
+
+    /// Limits how many tasks can be running before `try_spawn` refuses
+    /// more and `wait_and_spawn` waits. By default there's no limit.
+    pub fn max_tasks(self, max: usize) -> Executor {
+        assert!(max > 0, "an executor must admit at least one task");
+        self.shared.max_tasks.store(max, Ordering::SeqCst);
+        self
+    }
+
+    /// Calls `hook` with every panic from a task, as well as handing it to
+    /// the task's `JoinHandle`. The other tasks carry on either way.
+    pub fn on_panic(self, hook: impl Fn(&JoinError) + Send + Sync + 'static) -> Executor {
+        self.shared.panics.lock().unwrap().hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Aborts the process when a task panics, after calling the hook set
+    /// with `on_panic`
+    pub fn abort_on_panic(self) -> Executor {
+        self.shared.panics.lock().unwrap().abort = true;
+        self
+    }
+
+    /// Sets how many times a task can find what it polls ready before it
+    /// has to let the other tasks run. Locks, channels, timers, sockets and
+    /// join handles count towards it.
+    pub fn poll_budget(self, polls: u32) -> Executor {
+        assert!(polls > 0, "a task must be able to poll at least once");
+        self.shared.poll_budget.store(polls, Ordering::SeqCst);
+        self
+    }
+
+    /// Sets how long polling a task can take before the slow poll hook is
+    /// called
+    pub fn slow_poll_threshold(self, threshold: Duration) -> Executor {
+        let nanos = threshold.as_nanos().min(u64::MAX as u128) as u64;
+        self.shared.slow_poll_nanos.store(nanos, Ordering::SeqCst);
+        self
+    }
+
+    /// Calls `hook` with a task and how long polling it took, whenever that
+    /// was longer than the slow poll threshold. By default it prints a
+    /// warning.
+    pub fn on_slow_poll(self, hook: impl Fn(&TaskInfo, Duration) + Send + Sync + 'static) -> Executor {
+        *self.shared.slow_poll_hook.lock().unwrap() = Arc::new(hook);
+        self
+    }
+
+    pub fn monitor(&self) -> Monitor {
+        Monitor {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// A spawner for tasks of `Priority::Normal`
+    pub fn spawner(&self) -> Spawner {
+        self.shared.spawners.fetch_add(1, Ordering::SeqCst);
+        Spawner {
+            shared: self.shared.clone(),
+            priority: Priority::Normal,
+        }
+    }
+
+    /// Runs tasks on the worker threads until every spawner has been
+    /// dropped and every task has finished
+    pub fn run(self) {
+        let Executor { shared, queues } = self;
+        shared.idle.lock().unwrap().running = true;
+        // Nothing was ever spawned, or everything finished already
+        shared.shut_down_if_done();
+
+        thread::scope(|scope| {
+            for (index, queue) in queues.into_iter().enumerate() {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("executor-worker-{}", index))
+                    .spawn_scoped(scope, move || run_worker(shared, queue, index))
+                    .expect("failed to start a worker thread");
+            }
+        });
+
+        // Finished tasks that were woken again, which would otherwise keep
+        // `shared` alive through their references back to it
+        for injector in &shared.injectors {
+            while !injector.steal().is_empty() {}
+        }
+    }
+}
+
+impl Spawner {
+    /// Another spawner for the same executor, whose tasks have `priority`
+    pub fn with_priority(&self, priority: Priority) -> Spawner {
+        let mut spawner = self.clone();
+        spawner.priority = priority;
+        spawner
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn monitor(&self) -> Monitor {
+        Monitor {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Starts running `future` as a new task, the handle resolves to its
+    /// output. It's always admitted, but counts towards the limit set with
+    /// `Executor::max_tasks`.
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.tasks.fetch_add(1, Ordering::SeqCst);
+        self.start(future, None, Location::caller())
+    }
+
+    /// Like `spawn`, with a name for the task to go by in the `Monitor`
+    #[track_caller]
+    pub fn spawn_named<F>(&self, name: impl Into<String>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.tasks.fetch_add(1, Ordering::SeqCst);
+        self.start(future, Some(name.into()), Location::caller())
+    }
+
+    /// Like `spawn`, unless the executor already has as many tasks as it
+    /// will admit
+    #[track_caller]
+    pub fn try_spawn<F>(&self, future: F) -> Result<JoinHandle<F::Output>, SpawnError<F>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        if self.shared.try_admit() {
+            Ok(self.start(future, None, Location::caller()))
+        } else {
+            Err(SpawnError { future })
+        }
+    }
+
+    /// Like `spawn`, but first waits until the executor will admit another
+    /// task. A task waiting here counts towards the limit itself, so it
+    /// waits forever if the limit is one.
+    #[track_caller]
+    pub fn wait_and_spawn<F>(&self, future: F) -> impl Future<Output = JoinHandle<F::Output>> + '_
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let location = Location::caller();
+        futures::future::poll_fn(move |cx| self.shared.poll_admit(cx))
+            .map(move |()| self.start(future, None, location))
+    }
+
+    /// Queues `future` as a task that has already been counted
+    fn start<F>(
+        &self,
+        future: F,
+        name: Option<String>,
+        location: &'static Location<'static>,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let panics = self.shared.panics.lock().unwrap().clone();
+        let (future, handle) = join_pair(future, panics);
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(future.boxed())),
+            shared: self.shared.clone(),
+            priority: self.priority,
+            stats: self.shared.registry.register(name, location, self.priority),
+        });
+        handle.attach(&task);
+        self.shared.schedule(task);
+        handle
+    }
+}
+
+impl Clone for Spawner {
+    fn clone(&self) -> Spawner {
+        self.shared.spawners.fetch_add(1, Ordering::SeqCst);
+        Spawner {
+            shared: self.shared.clone(),
+            priority: self.priority,
+        }
+    }
+}
+
+impl Drop for Spawner {
+    fn drop(&mut self) {
+        if self.shared.spawners.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shared.shut_down_if_done();
+        }
+    }
+}
+
+impl Shared {
+    /// Queues a ready task, locally if this is one of our workers
+    fn schedule(self: &Arc<Self>, task: Arc<Task>) {
+        task.stats.scheduled();
+        let priority = task.priority.index();
+        // A task waking itself, say to yield, goes behind the tasks waiting
+        // in the injector rather than ahead of them
+        let task = WORKER.with(|worker| match *worker.borrow() {
+            Some(ref worker)
+                if Arc::ptr_eq(&worker.shared, self)
+                    && worker.polling.get() != Some(Arc::as_ptr(&task)) =>
+            {
+                worker.queues[priority].push(task);
+                None
+            }
+            _ => Some(task),
+        });
+        if let Some(task) = task {
+            self.injectors[priority].push(task);
+        }
+
+        // Someone idle can take it, or the work this worker was doing
+        let idle = self.idle.lock().unwrap();
+        if idle.sleeping > 0 {
+            self.wakeup.notify_one();
+        } else if idle.polling {
+            self.reactor.wake();
+        }
+    }
+
+    /// Takes a batch of tasks of one priority from the injector, or steals
+    /// one from another worker
+    fn steal(&self, queue: &Worker<Arc<Task>>, index: usize, priority: usize) -> Option<Arc<Task>> {
+        let count = self.stealers.len();
+        iter::repeat_with(|| {
+            self.injectors[priority].steal_batch_and_pop(queue).or_else(|| {
+                (1..count)
+                    .map(|i| self.stealers[(index + i) % count][priority].steal())
+                    .collect()
+            })
+        })
+        .find(|steal| !steal.is_retry())
+        .and_then(Steal::success)
+    }
+
+    fn has_work(&self) -> bool {
+        self.injectors.iter().any(|injector| !injector.is_empty())
+            || self.stealers.iter().flatten().any(|stealer| !stealer.is_empty())
+    }
+
+    /// Waits for work, a ready socket or until the next timer is due,
+    /// returning false once the executor is shutting down
+    fn sleep(&self, next_timer: Option<Instant>) -> bool {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.shutdown {
+            return false;
+        }
+        // Checked again while holding the lock, so that work scheduled
+        // after the worker last looked can't go unnoticed
+        if self.has_work() {
+            return true;
+        }
+
+        let timeout = next_timer.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if !idle.polling {
+            // Work scheduled once the lock is released wakes the reactor,
+            // even if this worker hasn't started waiting on it yet
+            idle.polling = true;
+            drop(idle);
+            self.reactor.turn(timeout);
+            idle = self.idle.lock().unwrap();
+            idle.polling = false;
+        } else {
+            idle.sleeping += 1;
+            idle = match timeout {
+                Some(timeout) => self.wakeup.wait_timeout(idle, timeout).unwrap().0,
+                None => self.wakeup.wait(idle).unwrap(),
+            };
+            idle.sleeping -= 1;
+        }
+        !idle.shutdown
+    }
+
+    /// Counts a new task, if there's room for it
+    fn try_admit(&self) -> bool {
+        let max = self.max_tasks.load(Ordering::SeqCst);
+        self.tasks
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |tasks| {
+                if tasks < max {
+                    Some(tasks + 1)
+                } else {
+                    None
+                }
+            })
+            .is_ok()
+    }
+
+    fn poll_admit(&self, cx: &mut Context) -> Poll<()> {
+        if self.try_admit() {
+            return Poll::Ready(());
+        }
+        self.admission_waiters.lock().unwrap().push(cx.waker().clone());
+        // Tried again in case a task finished before the waker was stored
+        if self.try_admit() {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+
+    /// Reports a panic that escaped a task, from dropping its future
+    fn task_panicked(&self, payload: Box<dyn Any + Send>) {
+        let panics = self.panics.lock().unwrap().clone();
+        panics.task_panicked(&JoinError::panic(payload));
+    }
+
+    /// Calls the slow poll hook if polling a task took too long
+    fn task_polled(&self, stats: &TaskStats, took: Duration) {
+        if took.as_nanos() > u128::from(self.slow_poll_nanos.load(Ordering::Relaxed)) {
+            let hook = self.slow_poll_hook.lock().unwrap().clone();
+            hook(&stats.info(), took);
+        }
+    }
+
+    fn task_finished(&self) {
+        if self.tasks.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shut_down_if_done();
+        }
+        // All of them, as any that has since been dropped can't pass the
+        // room on. The ones that lose the race wait again.
+        let waiters = std::mem::take(&mut *self.admission_waiters.lock().unwrap());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    fn shut_down_if_done(&self) {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.running
+            && self.tasks.load(Ordering::SeqCst) == 0
+            && self.spawners.load(Ordering::SeqCst) == 0
+        {
+            idle.shutdown = true;
+            drop(idle);
+            self.wakeup.notify_all();
+            self.reactor.wake();
+        }
+    }
+}
+
+fn run_worker(shared: Arc<Shared>, queues: Vec<Worker<Arc<Task>>>, index: usize) {
+    WORKER.with(|worker| {
+        *worker.borrow_mut() = Some(WorkerContext {
+            shared: shared.clone(),
+            queues,
+            index,
+            polling: Cell::new(None),
+        })
+    });
+    let _timers = shared.timers.enter();
+    let _reactor = shared.reactor.enter();
+
+    let mut polls = 0u32;
+    loop {
+        // Timers and sockets are only waited on when a worker is idle,
+        // unless they're checked now and then while busy
+        polls = polls.wrapping_add(1);
+        let check = polls.is_multiple_of(CHECK_INTERVAL);
+        if check {
+            shared.timers.fire_expired();
+            shared.reactor.turn(Some(Duration::from_secs(0)));
+        }
+
+        // The borrow must end before polling, wakers may need it
+        let task = WORKER.with(|worker| {
+            let worker = worker.borrow();
+            let worker = worker.as_ref().unwrap();
+            let task = worker.next_task(check);
+            worker.polling.set(task.as_ref().map(Arc::as_ptr));
+            task
+        });
+        if let Some(task) = task {
+            task.poll();
+            WORKER.with(|worker| worker.borrow().as_ref().unwrap().polling.set(None));
+            continue;
+        }
+
+        match shared.timers.fire_expired() {
+            (0, next_timer) if !shared.sleep(next_timer) => break,
+            _ => {}
+        }
+    }
+
+    WORKER.with(|worker| worker.borrow_mut().take());
+}
+
+impl WorkerContext {
+    /// Takes a task of the highest priority there is one of. When `check`
+    /// is set, takes one of the lowest priority instead, and looks at the
+    /// injector before the local queue, so that tasks waking each other on
+    /// this worker can't keep the injected ones waiting forever.
+    fn next_task(&self, check: bool) -> Option<Arc<Task>> {
+        let take = |priority: usize| {
+            let queue = &self.queues[priority];
+            let injected = if check {
+                self.shared.injectors[priority].steal_batch_and_pop(queue).success()
+            } else {
+                None
+            };
+            injected
+                .or_else(|| queue.pop())
+                .or_else(|| self.shared.steal(queue, self.index, priority))
+        };
+        if check {
+            (0..PRIORITIES).rev().find_map(take)
+        } else {
+            (0..PRIORITIES).find_map(take)
+        }
+    }
+}
+
+impl Priority {
+    /// Which queues tasks of this priority go on, highest first
+    fn index(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+impl Task {
+    fn poll(self: &Arc<Self>) {
+        let mut future_slot = self.future.lock().unwrap();
+        if let Some(mut future) = future_slot.take() {
+            let waker = waker_ref(self);
+            let context = &mut Context::from_waker(&waker);
+
+            let budget = self.shared.poll_budget.load(Ordering::Relaxed);
+            self.stats.running();
+            let started = Instant::now();
+
+            // The future's own panics go to its handle, this catches any
+            // from dropping it, so the worker and the lock survive either way
+            let polled = panic::catch_unwind(AssertUnwindSafe(|| {
+                let poll = coop::with_budget(budget, || future.as_mut().poll(context));
+                let pending = poll.is_pending();
+                (pending, if pending { Some(future) } else { None })
+            }));
+
+            let took = started.elapsed();
+            self.stats.polled(took);
+            self.shared.task_polled(&self.stats, took);
+            match polled {
+                Ok((true, future)) => {
+                    // We're not done, put the future back
+                    *future_slot = future;
+                }
+                Ok((false, _)) => {
+                    drop(future_slot);
+                    self.finished();
+                }
+                Err(payload) => {
+                    drop(future_slot);
+                    self.shared.task_panicked(payload);
+                    self.finished();
+                }
+            }
+        }
+    }
+
+    fn finished(&self) {
+        self.shared.registry.remove(self.stats.id);
+        self.shared.task_finished();
+    }
+}
+
+impl ArcWake for Task {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.shared.schedule(arc_self.clone());
+    }
+}
+
+impl Schedule for Task {
+    fn schedule(self: Arc<Self>) {
+        self.shared.clone().schedule(self);
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        // Dropped before finishing because nothing was left to wake it
+        let future = self.future.get_mut().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(future) = future {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| drop(future))) {
+                self.shared.task_panicked(payload);
+            }
+            self.finished();
+        }
+    }
+}
+
+impl<F> SpawnError<F> {
+    /// The future that wasn't spawned
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F> fmt::Display for SpawnError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "too many tasks are running")
+    }
+}
+
+impl<F> fmt::Debug for SpawnError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SpawnError")
+    }
+}
+
+impl<F> Error for SpawnError<F> {}
@@ -0,0 +1,192 @@
+//! Seeing what an executor's tasks are up to, for when something hangs.
+//!
+//! Every task keeps a few statistics that its worker updates as it polls
+//! it, and the executor keeps them for each task that hasn't finished. A
+//! `Monitor` copies them out, without counting as a spawner, so holding
+//! one doesn't stop the executor from finishing.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use super::{Priority, Shared};
+
+/// Lists the tasks of an executor that haven't finished
+#[derive(Clone)]
+pub struct Monitor {
+    pub(super) shared: Arc<Shared>,
+}
+
+/// What a task was doing when the `Monitor` looked at it
+#[derive(Clone, Debug)]
+pub struct TaskInfo {
+    pub id: u64,
+    pub name: Option<String>,
+    /// Where it was spawned
+    pub location: &'static Location<'static>,
+    pub priority: Priority,
+    pub state: TaskState,
+    /// How many times it has been polled
+    pub polls: u64,
+    /// How long polling it has taken altogether
+    pub poll_time: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskState {
+    /// Waiting to be woken
+    Idle,
+    /// Woken, and waiting for a worker to poll it
+    Scheduled,
+    /// Being polled
+    Running,
+}
+
+/// The statistics of one task, shared between the task and the executor
+pub(crate) struct TaskStats {
+    pub(crate) id: u64,
+    name: Option<String>,
+    location: &'static Location<'static>,
+    priority: Priority,
+    state: AtomicU8,
+    polls: AtomicU64,
+    poll_nanos: AtomicU64,
+}
+
+/// The tasks of an executor that haven't finished
+pub(crate) struct Registry {
+    tasks: Mutex<HashMap<u64, Arc<TaskStats>>>,
+    next_id: AtomicU64,
+}
+
+impl Monitor {
+    /// The tasks that haven't finished, in the order they were spawned
+    pub fn tasks(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<_> = {
+            let tasks = self.shared.registry.tasks.lock().unwrap();
+            tasks.values().map(|stats| stats.info()).collect()
+        };
+        tasks.sort_by_key(|task| task.id);
+        tasks
+    }
+}
+
+impl fmt::Display for Monitor {
+    /// One line for each task
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tasks = self.tasks();
+        writeln!(f, "{} tasks", tasks.len())?;
+        for task in tasks {
+            writeln!(f, "  {}", task)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TaskInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {}", self.id)?;
+        if let Some(ref name) = self.name {
+            write!(f, " {:?}", name)?;
+        }
+        write!(
+            f,
+            " spawned at {}: {}, {:?} priority, polled {} times for {:?}",
+            self.location, self.state, self.priority, self.polls, self.poll_time
+        )
+    }
+}
+
+impl fmt::Display for TaskState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TaskState::Idle => write!(f, "idle"),
+            TaskState::Scheduled => write!(f, "scheduled"),
+            TaskState::Running => write!(f, "running"),
+        }
+    }
+}
+
+impl TaskState {
+    fn from_u8(state: u8) -> TaskState {
+        match state {
+            0 => TaskState::Idle,
+            1 => TaskState::Scheduled,
+            _ => TaskState::Running,
+        }
+    }
+}
+
+impl TaskStats {
+    pub(crate) fn info(&self) -> TaskInfo {
+        TaskInfo {
+            id: self.id,
+            name: self.name.clone(),
+            location: self.location,
+            priority: self.priority,
+            state: TaskState::from_u8(self.state.load(Ordering::SeqCst)),
+            polls: self.polls.load(Ordering::Relaxed),
+            poll_time: Duration::from_nanos(self.poll_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub(crate) fn scheduled(&self) {
+        self.state.store(TaskState::Scheduled as u8, Ordering::SeqCst);
+    }
+
+    pub(crate) fn running(&self) {
+        self.state.store(TaskState::Running as u8, Ordering::SeqCst);
+    }
+
+    /// Counts a poll. Unless the task was woken while it was being polled,
+    /// it's idle now.
+    pub(crate) fn polled(&self, took: Duration) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.poll_nanos.fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+        let _ = self.state.compare_exchange(
+            TaskState::Running as u8,
+            TaskState::Idle as u8,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+    }
+}
+
+impl Registry {
+    pub(crate) fn new() -> Registry {
+        Registry {
+            tasks: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Gives a new task its id and starts keeping its statistics
+    pub(crate) fn register(
+        &self,
+        name: Option<String>,
+        location: &'static Location<'static>,
+        priority: Priority,
+    ) -> Arc<TaskStats> {
+        let stats = Arc::new(TaskStats {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            name,
+            location,
+            priority,
+            state: AtomicU8::new(TaskState::Scheduled as u8),
+            polls: AtomicU64::new(0),
+            poll_nanos: AtomicU64::new(0),
+        });
+        self.tasks.lock().unwrap().insert(stats.id, stats.clone());
+        stats
+    }
+
+    pub(crate) fn remove(&self, id: u64) {
+        self.tasks.lock().unwrap().remove(&id);
+    }
+}
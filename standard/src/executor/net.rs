@@ -0,0 +1,110 @@
+//! TCP sockets for tasks running on an executor
+
+use std::{
+    io::{self, Read, Write},
+    net::{self, Shutdown, SocketAddr, ToSocketAddrs},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{
+    future::poll_fn,
+    io::{AsyncRead, AsyncWrite},
+};
+use super::reactor::{Direction, PollEvented};
+
+/// Accepts TCP connections without blocking the executor
+pub struct AsyncTcpListener {
+    io: PollEvented<mio::net::TcpListener>,
+}
+
+/// A TCP connection that reads and writes without blocking the executor
+pub struct AsyncTcpStream {
+    io: PollEvented<mio::net::TcpStream>,
+}
+
+impl AsyncTcpListener {
+    /// Binds straight away, so the port is taken even before the listener
+    /// is first used on an executor
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<AsyncTcpListener> {
+        let listener = net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(AsyncTcpListener {
+            io: PollEvented::new(mio::net::TcpListener::from_std(listener)),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().local_addr()
+    }
+
+    pub async fn accept(&mut self) -> io::Result<(AsyncTcpStream, SocketAddr)> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    pub fn poll_accept(&mut self, cx: &mut Context) -> Poll<io::Result<(AsyncTcpStream, SocketAddr)>> {
+        self.io.poll_io(Direction::Read, cx, |listener| listener.accept()).map_ok(|(stream, addr)| {
+            let stream = AsyncTcpStream {
+                io: PollEvented::new(stream),
+            };
+            (stream, addr)
+        })
+    }
+}
+
+impl AsyncTcpStream {
+    pub async fn connect(addr: SocketAddr) -> io::Result<AsyncTcpStream> {
+        let mut stream = AsyncTcpStream {
+            io: PollEvented::new(mio::net::TcpStream::connect(addr)?),
+        };
+        // Connected once it's writable and has a peer
+        poll_fn(|cx| {
+            stream.io.poll_io(Direction::Write, cx, |stream| {
+                if let Some(err) = stream.take_error()? {
+                    return Err(err);
+                }
+                match stream.peer_addr() {
+                    Ok(_) => Ok(()),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotConnected => {
+                        Err(io::ErrorKind::WouldBlock.into())
+                    }
+                    Err(err) => Err(err),
+                }
+            })
+        })
+        .await?;
+        Ok(stream)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().peer_addr()
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.io.get_ref().shutdown(how)
+    }
+}
+
+impl AsyncRead for AsyncTcpStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.io.poll_io(Direction::Read, cx, |stream| stream.read(buf))
+    }
+}
+
+impl AsyncWrite for AsyncTcpStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.io.poll_io(Direction::Write, cx, |stream| stream.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        // Writes go straight to the socket
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(self.shutdown(Shutdown::Write))
+    }
+}
@@ -0,0 +1,270 @@
+//! Waking tasks when their sockets are ready, using epoll through mio.
+//!
+//! Each executor has one reactor. One idle worker at a time waits on it,
+//! with the other idle workers waiting on the executor's condvar. Sources
+//! are registered edge-triggered, so each one remembers whether it's ready
+//! until an operation on it fails with `WouldBlock`.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use mio::{event::Source, Events, Interest, Registry, Token};
+use super::coop;
+
+/// Reserved for the waker that interrupts a waiting worker
+const WAKE_TOKEN: Token = Token(usize::MAX);
+
+/// How many events one turn of the reactor handles at most
+const EVENTS_PER_TURN: usize = 256;
+
+pub(crate) struct Reactor {
+    poll: Mutex<(mio::Poll, Events)>,
+    registry: Registry,
+    waker: mio::Waker,
+    sources: Mutex<Sources>,
+}
+
+struct Sources {
+    readiness: HashMap<Token, Arc<Mutex<Readiness>>>,
+    next_token: usize,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Direction {
+    Read,
+    Write,
+}
+
+/// Whether a source can be read from and written to without blocking
+struct Readiness {
+    read: DirectionState,
+    write: DirectionState,
+}
+
+struct DirectionState {
+    ready: bool,
+    /// Counts readiness events, so that an operation that raced with one
+    /// doesn't clear the readiness it brought
+    tick: u64,
+    waker: Option<Waker>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Reactor>>> = const { RefCell::new(None) };
+}
+
+/// Puts back whichever reactor was current before, when dropped
+pub(crate) struct Enter {
+    previous: Option<Arc<Reactor>>,
+}
+
+impl Reactor {
+    pub(crate) fn new() -> io::Result<Reactor> {
+        let poll = mio::Poll::new()?;
+        let registry = poll.registry().try_clone()?;
+        let waker = mio::Waker::new(&registry, WAKE_TOKEN)?;
+        Ok(Reactor {
+            poll: Mutex::new((poll, Events::with_capacity(EVENTS_PER_TURN))),
+            registry,
+            waker,
+            sources: Mutex::new(Sources {
+                readiness: HashMap::new(),
+                next_token: 0,
+            }),
+        })
+    }
+
+    /// Makes this the reactor for sockets polled on the current thread,
+    /// until the guard is dropped
+    pub(crate) fn enter(self: &Arc<Self>) -> Enter {
+        let previous = CURRENT.with(|current| current.borrow_mut().replace(self.clone()));
+        Enter { previous }
+    }
+
+    /// Waits up to `timeout` for sources to become ready and wakes the tasks
+    /// waiting on them. Returns straight away if another thread is already
+    /// waiting.
+    pub(crate) fn turn(&self, timeout: Option<Duration>) {
+        let mut guard = match self.poll.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+        let (ref mut poll, ref mut events) = *guard;
+        match poll.poll(events, timeout) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => return,
+            Err(err) => panic!("failed to poll the reactor: {}", err),
+        }
+
+        let mut wakers = Vec::new();
+        {
+            let sources = self.sources.lock().unwrap();
+            for event in events.iter() {
+                let readiness = match sources.readiness.get(&event.token()) {
+                    Some(readiness) => readiness,
+                    // The waker, or a source deregistered since
+                    None => continue,
+                };
+                let mut readiness = readiness.lock().unwrap();
+                if event.is_readable() || event.is_read_closed() || event.is_error() {
+                    wakers.extend(readiness.read.set_ready());
+                }
+                if event.is_writable() || event.is_write_closed() || event.is_error() {
+                    wakers.extend(readiness.write.set_ready());
+                }
+            }
+        }
+        drop(guard);
+
+        // Woken outside the locks, waking schedules tasks
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Interrupts the thread waiting in `turn`, or the next one to wait
+    pub(crate) fn wake(&self) {
+        self.waker.wake().expect("failed to wake the reactor");
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+fn current() -> Arc<Reactor> {
+    CURRENT.with(|current| current.borrow().clone()).expect("sockets must be polled on an executor")
+}
+
+impl DirectionState {
+    fn new() -> DirectionState {
+        // Assumed ready until an operation says otherwise
+        DirectionState {
+            ready: true,
+            tick: 0,
+            waker: None,
+        }
+    }
+
+    fn set_ready(&mut self) -> Option<Waker> {
+        self.ready = true;
+        self.tick = self.tick.wrapping_add(1);
+        self.waker.take()
+    }
+}
+
+impl Readiness {
+    fn direction(&mut self, direction: Direction) -> &mut DirectionState {
+        match direction {
+            Direction::Read => &mut self.read,
+            Direction::Write => &mut self.write,
+        }
+    }
+}
+
+/// A socket that registers with the current executor's reactor the first
+/// time it's polled, and deregisters when it's dropped
+pub(crate) struct PollEvented<S: Source> {
+    source: S,
+    registration: Option<Registration>,
+}
+
+struct Registration {
+    reactor: Arc<Reactor>,
+    token: Token,
+    readiness: Arc<Mutex<Readiness>>,
+}
+
+impl<S: Source> PollEvented<S> {
+    pub(crate) fn new(source: S) -> PollEvented<S> {
+        PollEvented {
+            source,
+            registration: None,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Runs `op` once the source is ready in `direction`, trying again each
+    /// time it would block
+    pub(crate) fn poll_io<R>(
+        &mut self,
+        direction: Direction,
+        cx: &mut Context,
+        mut op: impl FnMut(&mut S) -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        futures::ready!(coop::poll_proceed(cx));
+        let readiness = match self.registration {
+            Some(ref registration) => registration.readiness.clone(),
+            None => self.register()?.readiness.clone(),
+        };
+        loop {
+            let tick = {
+                let mut readiness = readiness.lock().unwrap();
+                let state = readiness.direction(direction);
+                if !state.ready {
+                    state.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+                state.tick
+            };
+
+            match op(&mut self.source) {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    let mut readiness = readiness.lock().unwrap();
+                    let state = readiness.direction(direction);
+                    if state.tick == tick {
+                        state.ready = false;
+                    }
+                }
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn register(&mut self) -> io::Result<&Registration> {
+        let reactor = current();
+        let readiness = Arc::new(Mutex::new(Readiness {
+            read: DirectionState::new(),
+            write: DirectionState::new(),
+        }));
+        let token = {
+            let mut sources = reactor.sources.lock().unwrap();
+            let token = Token(sources.next_token);
+            sources.next_token += 1;
+            sources.readiness.insert(token, readiness.clone());
+            token
+        };
+
+        let interest = Interest::READABLE | Interest::WRITABLE;
+        if let Err(err) = reactor.registry.register(&mut self.source, token, interest) {
+            reactor.sources.lock().unwrap().readiness.remove(&token);
+            return Err(err);
+        }
+        Ok(self.registration.get_or_insert(Registration {
+            reactor,
+            token,
+            readiness,
+        }))
+    }
+}
+
+impl<S: Source> Drop for PollEvented<S> {
+    fn drop(&mut self) {
+        if let Some(registration) = self.registration.take() {
+            let reactor = registration.reactor;
+            let _ = reactor.registry.deregister(&mut self.source);
+            reactor.sources.lock().unwrap().readiness.remove(&registration.token);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Child tasks that can't outlive the task that spawned them
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+use super::{AbortHandle, JoinHandle, Spawner};
+
+/// Spawns child tasks for `Spawner::scope`. When the scope ends, it waits
+/// for the children that are still running, or aborts them if the scope
+/// itself is cancelled.
+#[derive(Clone)]
+pub struct Scope {
+    spawner: Spawner,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    /// Children still running, by an id the child's guard knows them by.
+    /// A child is counted before it's spawned, so it has no handle yet.
+    running: HashMap<u64, Option<AbortHandle>>,
+    next_id: u64,
+    /// Children with lower ids were running when the scope was cancelled
+    cancelled_below: u64,
+    /// Waiting for the children to finish
+    waker: Option<Waker>,
+    /// Set once the scope has ended, after which children are aborted as
+    /// soon as they're spawned
+    closed: bool,
+}
+
+/// Moves with the child's future, so that it's dropped however the child
+/// ends: finished, panicked or aborted
+struct ChildGuard {
+    state: Arc<Mutex<State>>,
+    id: u64,
+}
+
+/// Aborts the children if the scope doesn't get to wait for them
+struct CloseOnDrop {
+    state: Arc<Mutex<State>>,
+}
+
+impl Spawner {
+    /// Runs `body` with a scope for spawning child tasks. Once `body` has
+    /// finished, waits until every child has too. Dropping the returned
+    /// future aborts the children instead.
+    pub async fn scope<B, F>(&self, body: B) -> F::Output
+    where
+        B: FnOnce(Scope) -> F,
+        F: Future,
+    {
+        let scope = Scope {
+            spawner: self.clone(),
+            state: Arc::new(Mutex::new(State {
+                running: HashMap::new(),
+                next_id: 0,
+                cancelled_below: 0,
+                waker: None,
+                closed: false,
+            })),
+        };
+        let _close = CloseOnDrop {
+            state: scope.state.clone(),
+        };
+
+        let output = body(scope.clone()).await;
+        futures::future::poll_fn(|cx| scope.poll_children(cx)).await;
+        output
+    }
+}
+
+impl Scope {
+    /// Like `Spawner::spawn`, for a child of this scope
+    #[track_caller]
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.running.insert(id, None);
+            id
+        };
+        let guard = ChildGuard {
+            state: self.state.clone(),
+            id,
+        };
+        let handle = self.spawner.spawn(async move {
+            let _guard = guard;
+            future.await
+        });
+
+        let mut state = self.state.lock().unwrap();
+        // Unless the child has already finished
+        if let Some(child) = state.running.get_mut(&id) {
+            *child = Some(handle.abort_handle());
+        }
+        if state.closed || id < state.cancelled_below {
+            handle.abort();
+        }
+        handle
+    }
+
+    /// Aborts every child that is still running. The scope still waits for
+    /// them to be dropped.
+    pub fn cancel(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.cancelled_below = state.next_id;
+        for child in state.running.values().flatten() {
+            child.abort();
+        }
+    }
+
+    fn poll_children(&self, cx: &mut Context) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.running.is_empty() {
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.running.remove(&self.id);
+        if state.running.is_empty() {
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        for child in state.running.values().flatten() {
+            child.abort();
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! A deterministic executor for tests.
+//!
+//! A `Simulation` runs its tasks on one thread, picking which ready task to
+//! poll next with a random number generator seeded up front. Its clock is
+//! virtual: when no task is ready, it jumps straight to the next timer's
+//! deadline, so sleeping takes no real time. The same seed always gives
+//! the same interleaving, so a seed that makes a test fail replays it.
+//!
+//! Only wakers called while the simulation runs count. A task waiting on
+//! anything outside it, such as a socket or another thread, looks stuck.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+use futures::{
+    future::LocalBoxFuture,
+    task::{waker, ArcWake},
+    FutureExt,
+};
+use super::{
+    join::{join_pair, PanicPolicy, Schedule},
+    time, JoinHandle,
+};
+
+/// Stands for the future passed to `block_on` in the ready set
+const MAIN_TASK: usize = usize::MAX;
+
+/// Runs tasks in an order chosen by a seeded random number generator, with
+/// a clock that only moves when every task is waiting
+pub struct Simulation {
+    shared: Rc<SimShared>,
+}
+
+/// Spawns new futures onto a `Simulation`
+#[derive(Clone)]
+pub struct SimSpawner {
+    shared: Rc<SimShared>,
+}
+
+struct SimShared {
+    seed: u64,
+    /// The state of the random number generator
+    rng: Cell<u64>,
+    tasks: RefCell<HashMap<usize, SimTask>>,
+    next_id: Cell<usize>,
+    ready: Arc<ReadySet>,
+    timers: Arc<time::Driver>,
+    started: Instant,
+}
+
+struct SimTask {
+    /// Taken out while it's being polled, so that it can spawn
+    future: Option<LocalBoxFuture<'static, ()>>,
+    waker: Waker,
+}
+
+/// The ids of the tasks that have been woken. It's ordered, so that which
+/// one is picked depends only on the random number.
+struct ReadySet {
+    ids: Mutex<BTreeSet<usize>>,
+}
+
+/// Wakes one task of a `Simulation`
+struct TaskWaker {
+    id: usize,
+    ready: Arc<ReadySet>,
+}
+
+/// Runs `test` on a new simulation for each seed. If it panics, the seed is
+/// printed before the panic carries on, so that `Simulation::new` can
+/// replay it.
+pub fn check_seeds(seeds: Range<u64>, test: impl Fn(&Simulation)) {
+    for seed in seeds {
+        let simulation = Simulation::new(seed);
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| test(&simulation))) {
+            eprintln!("the simulation failed with seed {}", seed);
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Simulation {
+        let timers = time::Driver::new_virtual();
+        Simulation {
+            shared: Rc::new(SimShared {
+                seed,
+                rng: Cell::new(seed),
+                tasks: RefCell::new(HashMap::new()),
+                next_id: Cell::new(0),
+                ready: Arc::new(ReadySet {
+                    ids: Mutex::new(BTreeSet::new()),
+                }),
+                started: timers.now(),
+                timers: Arc::new(timers),
+            }),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.shared.seed
+    }
+
+    pub fn spawner(&self) -> SimSpawner {
+        SimSpawner {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// The time on the virtual clock
+    pub fn now(&self) -> Instant {
+        self.shared.timers.now()
+    }
+
+    /// How far the virtual clock has moved since the simulation was created
+    pub fn elapsed(&self) -> Duration {
+        self.now() - self.shared.started
+    }
+
+    /// Runs the spawned tasks until `future` completes, returning its
+    /// output. Tasks still running afterwards carry on at the next call.
+    ///
+    /// Panics if `future` can never complete because nothing is left that
+    /// could wake it.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let shared = &self.shared;
+        let _timers = shared.timers.enter();
+
+        futures::pin_mut!(future);
+        let main_waker = waker(Arc::new(TaskWaker {
+            id: MAIN_TASK,
+            ready: shared.ready.clone(),
+        }));
+        shared.ready.insert(MAIN_TASK);
+
+        loop {
+            match shared.next_ready() {
+                Some(MAIN_TASK) => {
+                    let context = &mut Context::from_waker(&main_waker);
+                    if let Poll::Ready(output) = future.as_mut().poll(context) {
+                        return output;
+                    }
+                }
+                Some(id) => shared.poll_task(id),
+                None => shared.deadlocked(),
+            }
+        }
+    }
+
+    /// Runs until every spawned task has finished
+    ///
+    /// Panics if some can never finish because nothing is left that could
+    /// wake them.
+    pub fn run(&self) {
+        let shared = &self.shared;
+        let _timers = shared.timers.enter();
+
+        loop {
+            match shared.next_ready() {
+                // Woken after its `block_on` returned
+                Some(MAIN_TASK) => {}
+                Some(id) => shared.poll_task(id),
+                None if shared.tasks.borrow().is_empty() => return,
+                None => shared.deadlocked(),
+            }
+        }
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        // Cancels the tasks that haven't finished, and breaks the cycle
+        // from tasks holding a spawner back to the simulation
+        let tasks = std::mem::take(&mut *self.shared.tasks.borrow_mut());
+        drop(tasks);
+    }
+}
+
+impl SimSpawner {
+    /// Starts running `future` as a new task, the handle resolves to its
+    /// output
+    pub fn spawn<F: Future + 'static>(&self, future: F) -> JoinHandle<F::Output> {
+        let (future, handle) = join_pair(future, PanicPolicy::default());
+        let shared = &self.shared;
+        let id = shared.next_id.get();
+        shared.next_id.set(id + 1);
+
+        let task_waker = Arc::new(TaskWaker {
+            id,
+            ready: shared.ready.clone(),
+        });
+        handle.attach(&task_waker);
+        let task = SimTask {
+            future: Some(future.boxed_local()),
+            waker: waker(task_waker),
+        };
+        shared.tasks.borrow_mut().insert(id, task);
+        shared.ready.insert(id);
+        handle
+    }
+}
+
+impl SimShared {
+    /// Picks one of the ready tasks at random. If none is ready, moves the
+    /// clock on until a timer wakes one, or returns `None` if there are no
+    /// timers left.
+    fn next_ready(&self) -> Option<usize> {
+        loop {
+            if let Some(id) = self.ready.take(self.random()) {
+                return Some(id);
+            }
+            let (fired, _) = self.timers.fire_expired();
+            if fired == 0 && !self.timers.advance() {
+                return None;
+            }
+        }
+    }
+
+    fn poll_task(&self, id: usize) {
+        // Finished already, or being polled further up the stack
+        let (mut future, waker) = match self.tasks.borrow_mut().get_mut(&id) {
+            Some(task) => match task.future.take() {
+                Some(future) => (future, task.waker.clone()),
+                None => return,
+            },
+            None => return,
+        };
+
+        if future.as_mut().poll(&mut Context::from_waker(&waker)).is_pending() {
+            if let Some(task) = self.tasks.borrow_mut().get_mut(&id) {
+                task.future = Some(future);
+            }
+        } else {
+            self.tasks.borrow_mut().remove(&id);
+        }
+    }
+
+    /// SplitMix64, which is plenty for shuffling tasks
+    fn random(&self) -> u64 {
+        let state = self.rng.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.rng.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn deadlocked(&self) -> ! {
+        panic!(
+            "the simulation with seed {} is stuck: {} tasks are waiting and nothing can wake them",
+            self.seed,
+            self.tasks.borrow().len()
+        );
+    }
+}
+
+impl ReadySet {
+    fn insert(&self, id: usize) {
+        self.ids.lock().unwrap().insert(id);
+    }
+
+    /// Removes the ready task that `random` picks
+    fn take(&self, random: u64) -> Option<usize> {
+        let mut ids = self.ids.lock().unwrap();
+        if ids.is_empty() {
+            return None;
+        }
+        let index = (random % ids.len() as u64) as usize;
+        let id = *ids.iter().nth(index).unwrap();
+        ids.remove(&id);
+        Some(id)
+    }
+}
+
+impl Schedule for TaskWaker {
+    fn schedule(self: Arc<Self>) {
+        ArcWake::wake(self);
+    }
+}
+
+impl ArcWake for TaskWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.ready.insert(arc_self.id);
+    }
+}
@@ -0,0 +1,241 @@
+//! Adapters for `futures::Stream`s, such as a channel's receiver or a
+//! timer's interval.
+//!
+//! They're methods of `StreamAdapters`, which has a few names in common
+//! with `futures::StreamExt`, so only one of the two should be in scope.
+//! Only `chunks_timeout` needs to run on our executor, for its timer.
+
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::stream::{FuturesUnordered, Stream};
+use super::time::{self, Sleep};
+
+/// Methods for wrapping a stream in one of the adapters
+pub trait StreamAdapters: Stream + Sized {
+    /// Completes with the next item, or `None` once the stream has ended
+    fn next(&mut self) -> Next<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Next { stream: self }
+    }
+
+    /// Passes each item through `f`
+    fn map<G, U>(self, f: G) -> Map<Self, G>
+    where
+        G: FnMut(Self::Item) -> U,
+    {
+        Map { stream: self, f }
+    }
+
+    /// Skips the items that `predicate` returns false for
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        P: FnMut(&Self::Item) -> bool,
+    {
+        Filter {
+            stream: self,
+            predicate,
+        }
+    }
+
+    /// Runs the futures the stream yields, up to `limit` of them at once,
+    /// and yields their outputs in the order they complete
+    fn buffer_unordered(self, limit: usize) -> BufferUnordered<Self>
+    where
+        Self::Item: Future,
+    {
+        assert!(limit > 0, "buffer_unordered needs a limit of at least one");
+        BufferUnordered {
+            stream: self,
+            in_flight: FuturesUnordered::new(),
+            limit,
+            ended: false,
+        }
+    }
+
+    /// Gathers the items into chunks of up to `max`. A chunk that isn't
+    /// full is yielded anyway once `duration` has passed since its first
+    /// item arrived, or when the stream ends.
+    fn chunks_timeout(self, max: usize, duration: Duration) -> ChunksTimeout<Self> {
+        assert!(max > 0, "chunks need room for at least one item");
+        ChunksTimeout {
+            stream: self,
+            chunk: Vec::with_capacity(max),
+            max,
+            duration,
+            deadline: None,
+            ended: false,
+        }
+    }
+}
+
+impl<S: Stream> StreamAdapters for S {}
+
+/// The future returned by `StreamAdapters::next`
+pub struct Next<'a, S> {
+    stream: &'a mut S,
+}
+
+impl<S: Stream + Unpin> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+/// The stream returned by `StreamAdapters::map`
+pub struct Map<S, G> {
+    stream: S,
+    f: G,
+}
+
+impl<S, G, U> Stream for Map<S, G>
+where
+    S: Stream,
+    G: FnMut(S::Item) -> U,
+{
+    type Item = U;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<U>> {
+        // SAFETY: `stream` is pinned whenever `self` is. It's never moved
+        // out and `Map` has no `Drop` impl. `f` isn't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+
+        stream.poll_next(cx).map(|item| item.map(&mut this.f))
+    }
+}
+
+/// The stream returned by `StreamAdapters::filter`
+pub struct Filter<S, P> {
+    stream: S,
+    predicate: P,
+}
+
+impl<S, P> Stream for Filter<S, P>
+where
+    S: Stream,
+    P: FnMut(&S::Item) -> bool,
+{
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<S::Item>> {
+        // SAFETY: as for `Map`, only `stream` is pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+
+        loop {
+            match futures::ready!(stream.as_mut().poll_next(cx)) {
+                Some(item) if !(this.predicate)(&item) => continue,
+                item => return Poll::Ready(item),
+            }
+        }
+    }
+}
+
+/// The stream returned by `StreamAdapters::buffer_unordered`
+pub struct BufferUnordered<S: Stream> {
+    stream: S,
+    in_flight: FuturesUnordered<S::Item>,
+    limit: usize,
+    /// The stream has ended, so it mustn't be polled again
+    ended: bool,
+}
+
+impl<S> Stream for BufferUnordered<S>
+where
+    S: Stream,
+    S::Item: Future,
+{
+    type Item = <S::Item as Future>::Output;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        // SAFETY: as for `Map`, only `stream` is pinned. `FuturesUnordered`
+        // pins the futures in it itself, and it's `Unpin`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+
+        // Starts as many futures as there's room for
+        while !this.ended && this.in_flight.len() < this.limit {
+            match stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(future)) => this.in_flight.push(future),
+                Poll::Ready(None) => this.ended = true,
+                Poll::Pending => break,
+            }
+        }
+
+        match Pin::new(&mut this.in_flight).poll_next(cx) {
+            Poll::Ready(Some(output)) => Poll::Ready(Some(output)),
+            // None in flight, but the stream might yield more
+            Poll::Ready(None) if !this.ended => Poll::Pending,
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// The stream returned by `StreamAdapters::chunks_timeout`
+pub struct ChunksTimeout<S: Stream> {
+    stream: S,
+    chunk: Vec<S::Item>,
+    max: usize,
+    duration: Duration,
+    /// When the chunk is due, set when its first item arrives
+    deadline: Option<Sleep>,
+    /// The stream has ended, so it mustn't be polled again
+    ended: bool,
+}
+
+impl<S: Stream> ChunksTimeout<S> {
+    fn take_chunk(&mut self) -> Vec<S::Item> {
+        self.deadline = None;
+        mem::replace(&mut self.chunk, Vec::with_capacity(self.max))
+    }
+}
+
+impl<S: Stream> Stream for ChunksTimeout<S> {
+    type Item = Vec<S::Item>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        // SAFETY: as for `Map`, only `stream` is pinned. `take_chunk`
+        // borrows `self` unpinned, but it only touches the other fields.
+        let this = unsafe { self.get_unchecked_mut() };
+
+        while !this.ended {
+            let stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+            match stream.poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    if this.chunk.is_empty() {
+                        this.deadline = Some(time::sleep(this.duration));
+                    }
+                    this.chunk.push(item);
+                    if this.chunk.len() >= this.max {
+                        return Poll::Ready(Some(this.take_chunk()));
+                    }
+                }
+                Poll::Ready(None) => this.ended = true,
+                Poll::Pending => {
+                    if let Some(ref mut deadline) = this.deadline {
+                        if Pin::new(deadline).poll(cx).is_ready() {
+                            return Poll::Ready(Some(this.take_chunk()));
+                        }
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
+
+        if this.chunk.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(this.take_chunk()))
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Waker-based synchronisation for tasks, so that waiting never blocks a
+//! worker thread. Nothing here depends on our executor, they work on any.
+//!
+//! Waiters are served in the order they started waiting, and a future that
+//! is dropped while waiting gives up its place without losing anything it
+//! had been handed.
+
+mod mutex;
+mod notify;
+mod rwlock;
+mod semaphore;
+
+pub mod mpsc;
+pub mod oneshot;
+
+pub use self::{
+    mutex::{Mutex, MutexGuard},
+    notify::{Notified, Notify},
+    rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    semaphore::{AcquireError, Semaphore, SemaphorePermit, TryAcquireError},
+};
@@ -0,0 +1,205 @@
+//! A bounded channel with any number of senders and one receiver
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+use futures::Stream;
+use crate::executor::coop;
+use super::Semaphore;
+
+/// Sends values to the receiver, waiting while the channel is full. Senders
+/// that are waiting get to send in the order they started waiting.
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+/// Receives the values in the order they were sent. It's also a `Stream`
+/// of them.
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+/// The receiver was dropped or closed, so the value couldn't be sent
+#[derive(PartialEq)]
+pub struct SendError<T>(pub T);
+
+/// The error from `Sender::try_send`
+#[derive(PartialEq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Closed(T),
+}
+
+struct Chan<T> {
+    /// One permit for each free slot in the queue
+    slots: Semaphore,
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    receiver_waker: Option<Waker>,
+    senders: usize,
+}
+
+/// Makes a channel that holds up to `capacity` values that haven't been
+/// received yet
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a channel needs room for at least one value");
+    let chan = Arc::new(Chan {
+        slots: Semaphore::new(capacity),
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            receiver_waker: None,
+            senders: 1,
+        }),
+    });
+    let sender = Sender { chan: chan.clone() };
+    (sender, Receiver { chan })
+}
+
+impl<T> Sender<T> {
+    /// Waits for room in the channel. If it's dropped while waiting, the
+    /// value is dropped too and its place in line goes to the next sender.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match self.chan.slots.acquire_raw(1).await {
+            Ok(()) => {
+                self.chan.push(value);
+                Ok(())
+            }
+            Err(_) => Err(SendError(value)),
+        }
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        match self.chan.slots.try_acquire() {
+            Ok(permit) => {
+                permit.forget();
+                self.chan.push(value);
+                Ok(())
+            }
+            Err(super::TryAcquireError::NoPermits) => Err(TrySendError::Full(value)),
+            Err(super::TryAcquireError::Closed) => Err(TrySendError::Closed(value)),
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.chan.slots.is_closed()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.chan.state.lock().unwrap().senders += 1;
+        Sender {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.chan.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            if let Some(waker) = state.receiver_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// The next value, or `None` once every value has been received and
+    /// either every sender has been dropped or the channel was closed
+    pub async fn recv(&mut self) -> Option<T> {
+        futures::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        futures::ready!(coop::poll_proceed(cx));
+        let mut state = self.chan.state.lock().unwrap();
+        if let Some(value) = state.queue.pop_front() {
+            drop(state);
+            self.chan.slots.add_permits(1);
+            return Poll::Ready(Some(value));
+        }
+        if state.senders == 0 || self.chan.slots.is_closed() {
+            return Poll::Ready(None);
+        }
+        state.receiver_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Stops any more values being sent. Those already sent can still be
+    /// received.
+    pub fn close(&mut self) {
+        self.chan.slots.close();
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.chan.slots.close();
+        let queue = std::mem::take(&mut self.chan.state.lock().unwrap().queue);
+        // Dropped outside the lock, their destructors might use the channel
+        drop(queue);
+    }
+}
+
+impl<T> Chan<T> {
+    fn push(&self, value: T) {
+        let mut state = self.state.lock().unwrap();
+        state.queue.push_back(value);
+        if let Some(waker) = state.receiver_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel closed")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TrySendError::Full(_) => f.write_str("Full(..)"),
+            TrySendError::Closed(_) => f.write_str("Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TrySendError::Full(_) => write!(f, "channel full"),
+            TrySendError::Closed(_) => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
@@ -0,0 +1,137 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+use crate::executor::coop;
+
+/// Wakes waiting tasks without any data to go with it. `notify_one` with
+/// nobody waiting is saved for the next task to wait, like a semaphore
+/// holding at most one permit.
+pub struct Notify {
+    state: Mutex<State>,
+}
+
+/// The future returned by `Notify::notified`
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    /// Set while it's in the queue
+    id: Option<u64>,
+}
+
+struct State {
+    /// A `notify_one` that nobody was waiting for
+    saved: bool,
+    waiters: VecDeque<(u64, Waker)>,
+    /// Waiters that have been notified but not yet seen it, and whether it
+    /// was by `notify_one` and so should be passed on if they're dropped
+    notified: HashMap<u64, bool>,
+    next_id: u64,
+}
+
+impl Notify {
+    pub fn new() -> Notify {
+        Notify {
+            state: Mutex::new(State {
+                saved: false,
+                waiters: VecDeque::new(),
+                notified: HashMap::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    /// Completes once notified. It joins the queue of waiters when it's
+    /// first polled.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            id: None,
+        }
+    }
+
+    /// Wakes the task that has been waiting longest, or the next to wait
+    pub fn notify_one(&self) {
+        self.state.lock().unwrap().notify_one();
+    }
+
+    /// Wakes every task waiting now, saving nothing for later ones
+    pub fn notify_waiters(&self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some((id, waker)) = state.waiters.pop_front() {
+            state.notified.insert(id, false);
+            waker.wake();
+        }
+    }
+}
+
+impl State {
+    fn notify_one(&mut self) {
+        match self.waiters.pop_front() {
+            Some((id, waker)) => {
+                self.notified.insert(id, true);
+                waker.wake();
+            }
+            None => self.saved = true,
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Notify {
+        Notify::new()
+    }
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        futures::ready!(coop::poll_proceed(cx));
+        let mut state = self.notify.state.lock().unwrap();
+        match self.id {
+            Some(id) if state.notified.remove(&id).is_some() => {
+                self.id = None;
+                Poll::Ready(())
+            }
+            Some(id) => {
+                let waiter = state.waiters.iter_mut().find(|waiter| waiter.0 == id).unwrap();
+                if !waiter.1.will_wake(cx.waker()) {
+                    waiter.1 = cx.waker().clone();
+                }
+                Poll::Pending
+            }
+            None if state.saved => {
+                state.saved = false;
+                Poll::Ready(())
+            }
+            None => {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.waiters.push_back((id, cx.waker().clone()));
+                drop(state);
+                self.id = Some(id);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let id = match self.id {
+            Some(id) => id,
+            None => return,
+        };
+        let mut state = self.notify.state.lock().unwrap();
+        match state.notified.remove(&id) {
+            // Notified but dropped before it could act on it, so it goes to
+            // the next in line instead of being lost
+            Some(true) => state.notify_one(),
+            Some(false) => {}
+            None => state.waiters.retain(|waiter| waiter.0 != id),
+        }
+    }
+}
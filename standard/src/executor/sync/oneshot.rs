@@ -0,0 +1,118 @@
+//! A channel for sending a single value
+
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+use crate::executor::coop;
+
+pub struct Sender<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+/// Resolves to the value, or an error if the sender was dropped without
+/// sending one
+pub struct Receiver<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+/// The sender was dropped without sending a value
+#[derive(Debug, PartialEq)]
+pub struct RecvError;
+
+struct Inner<T> {
+    value: Option<T>,
+    receiver_waker: Option<Waker>,
+    sender_dropped: bool,
+    receiver_dropped: bool,
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Mutex::new(Inner {
+        value: None,
+        receiver_waker: None,
+        sender_dropped: false,
+        receiver_dropped: false,
+    }));
+    let sender = Sender { inner: inner.clone() };
+    (sender, Receiver { inner })
+}
+
+impl<T> Sender<T> {
+    /// Gives back the value if the receiver has been dropped
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.receiver_dropped {
+            return Err(value);
+        }
+        inner.value = Some(value);
+        if let Some(waker) = inner.receiver_waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock().unwrap().receiver_dropped
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.sender_dropped = true;
+        if let Some(waker) = inner.receiver_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Stops the sender from sending, taking the value if it already has
+    pub fn close(&mut self) -> Option<T> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.receiver_dropped = true;
+        inner.value.take()
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        futures::ready!(coop::poll_proceed(cx));
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(value) = inner.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+        if inner.sender_dropped {
+            return Poll::Ready(Err(RecvError));
+        }
+        inner.receiver_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let value = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.receiver_dropped = true;
+            inner.value.take()
+        };
+        // Dropped outside the lock, its destructor might use the channel
+        drop(value);
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sender dropped without sending")
+    }
+}
+
+impl Error for RecvError {}
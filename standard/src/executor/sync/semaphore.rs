@@ -0,0 +1,246 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+use crate::executor::coop;
+
+/// Counts out permits to tasks in the order they asked for them. A task
+/// asking for more permits than are free holds up the ones behind it, so
+/// that it isn't starved by a stream of smaller requests.
+pub struct Semaphore {
+    state: Mutex<State>,
+}
+
+/// Returns its permits to the semaphore when dropped
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+/// The error from acquiring permits of a closed semaphore
+#[derive(Debug, PartialEq)]
+pub struct AcquireError;
+
+/// The error from `try_acquire`
+#[derive(Debug, PartialEq)]
+pub enum TryAcquireError {
+    Closed,
+    NoPermits,
+}
+
+struct State {
+    permits: usize,
+    waiters: VecDeque<Waiter>,
+    /// Waiters that have been handed their permits but not yet seen it
+    granted: HashSet<u64>,
+    next_id: u64,
+    closed: bool,
+}
+
+struct Waiter {
+    id: u64,
+    permits: usize,
+    waker: Waker,
+}
+
+/// Waits for permits. Dropping it gives back any it was granted, and lets
+/// the waiters behind it move up.
+pub(crate) struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+    /// Set while it's in the queue
+    id: Option<u64>,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                waiters: VecDeque::new(),
+                granted: HashSet::new(),
+                next_id: 0,
+                closed: false,
+            }),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().unwrap().permits
+    }
+
+    pub fn add_permits(&self, permits: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.permits += permits;
+        state.grant();
+    }
+
+    /// Fails every waiting and future `acquire`. Permits already handed out
+    /// stay valid.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        for waiter in state.waiters.drain(..) {
+            waiter.waker.wake();
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.acquire_many(1).await
+    }
+
+    pub async fn acquire_many(&self, permits: usize) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.acquire_raw(permits).await?;
+        Ok(SemaphorePermit {
+            semaphore: self,
+            permits,
+        })
+    }
+
+    /// Takes a permit if one is free and nobody is waiting ahead
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    pub fn try_acquire_many(&self, permits: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(TryAcquireError::Closed);
+        }
+        if !state.waiters.is_empty() || state.permits < permits {
+            return Err(TryAcquireError::NoPermits);
+        }
+        state.permits -= permits;
+        Ok(SemaphorePermit {
+            semaphore: self,
+            permits,
+        })
+    }
+
+    /// Permits taken this way must be given back with `add_permits`
+    pub(crate) fn acquire_raw(&self, permits: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            permits,
+            id: None,
+        }
+    }
+}
+
+impl State {
+    /// Hands out free permits to the waiters at the front of the queue
+    fn grant(&mut self) {
+        while let Some(waiter) = self.waiters.front() {
+            if waiter.permits > self.permits {
+                break;
+            }
+            let waiter = self.waiters.pop_front().unwrap();
+            self.permits -= waiter.permits;
+            self.granted.insert(waiter.id);
+            waiter.waker.wake();
+        }
+    }
+}
+
+impl Future for Acquire<'_> {
+    type Output = Result<(), AcquireError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        futures::ready!(coop::poll_proceed(cx));
+        let mut state = self.semaphore.state.lock().unwrap();
+        match self.id {
+            Some(id) if state.granted.remove(&id) => {
+                self.id = None;
+                Poll::Ready(Ok(()))
+            }
+            Some(_) if state.closed => {
+                self.id = None;
+                Poll::Ready(Err(AcquireError))
+            }
+            Some(id) => {
+                let waiter = state.waiters.iter_mut().find(|waiter| waiter.id == id).unwrap();
+                if !waiter.waker.will_wake(cx.waker()) {
+                    waiter.waker = cx.waker().clone();
+                }
+                Poll::Pending
+            }
+            None if state.closed => Poll::Ready(Err(AcquireError)),
+            None if state.waiters.is_empty() && state.permits >= self.permits => {
+                state.permits -= self.permits;
+                Poll::Ready(Ok(()))
+            }
+            None => {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.waiters.push_back(Waiter {
+                    id,
+                    permits: self.permits,
+                    waker: cx.waker().clone(),
+                });
+                drop(state);
+                self.id = Some(id);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let id = match self.id {
+            Some(id) => id,
+            None => return,
+        };
+        let mut state = self.semaphore.state.lock().unwrap();
+        if state.granted.remove(&id) {
+            state.permits += self.permits;
+        } else {
+            state.waiters.retain(|waiter| waiter.id != id);
+        }
+        // It may have been holding up the waiters behind it
+        state.grant();
+    }
+}
+
+impl SemaphorePermit<'_> {
+    /// Keeps the permits out of the semaphore for good
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "semaphore closed")
+    }
+}
+
+impl Error for AcquireError {}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TryAcquireError::Closed => write!(f, "semaphore closed"),
+            TryAcquireError::NoPermits => write!(f, "no permits available"),
+        }
+    }
+}
+
+impl Error for TryAcquireError {}
@@ -0,0 +1,348 @@
+//! Timers for tasks running on an executor.
+//!
+//! Each executor has one timer driver, a map of deadlines ordered by time.
+//! Idle workers fire the timers that are due and then park until the next
+//! deadline, so no timer needs a thread of its own. The map is a `BTreeMap`
+//! rather than a binary heap so that a timer dropped before it fires can be
+//! removed straight away instead of lingering until its deadline.
+//!
+//! A driver reads either the real clock or a virtual one, which stands
+//! still until it's moved on to the next deadline. Timers use whichever
+//! clock their executor's driver has, through `now`.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+use futures::Stream;
+use super::coop;
+
+/// The registered timers of one executor
+pub(crate) struct Driver {
+    state: Mutex<State>,
+    clock: Clock,
+}
+
+enum Clock {
+    Real,
+    /// Only moves when `advance` is called
+    Virtual(Mutex<Instant>),
+}
+
+struct State {
+    /// The id breaks ties between timers with the same deadline
+    timers: BTreeMap<(Instant, u64), Waker>,
+    next_id: u64,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Driver>>> = const { RefCell::new(None) };
+}
+
+/// Puts back whichever driver was current before, when dropped
+pub(crate) struct Enter {
+    previous: Option<Arc<Driver>>,
+}
+
+impl Driver {
+    pub(crate) fn new() -> Driver {
+        Driver::with_clock(Clock::Real)
+    }
+
+    /// A driver whose clock starts at the real time but then stands still
+    /// until it's advanced
+    pub(crate) fn new_virtual() -> Driver {
+        Driver::with_clock(Clock::Virtual(Mutex::new(Instant::now())))
+    }
+
+    fn with_clock(clock: Clock) -> Driver {
+        Driver {
+            state: Mutex::new(State {
+                timers: BTreeMap::new(),
+                next_id: 0,
+            }),
+            clock,
+        }
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        match self.clock {
+            Clock::Real => Instant::now(),
+            Clock::Virtual(ref now) => *now.lock().unwrap(),
+        }
+    }
+
+    /// Moves a virtual clock on to the next deadline, returning false if
+    /// there are no timers
+    pub(crate) fn advance(&self) -> bool {
+        let next = match self.state.lock().unwrap().timers.keys().next() {
+            Some(&(deadline, _)) => deadline,
+            None => return false,
+        };
+        match self.clock {
+            Clock::Real => panic!("the real clock can't be advanced"),
+            Clock::Virtual(ref now) => {
+                let mut now = now.lock().unwrap();
+                *now = next.max(*now);
+            }
+        }
+        true
+    }
+
+    /// Makes this the driver for timers polled on the current thread, until
+    /// the guard is dropped
+    pub(crate) fn enter(self: &Arc<Self>) -> Enter {
+        let previous = CURRENT.with(|current| current.borrow_mut().replace(self.clone()));
+        Enter { previous }
+    }
+
+    /// Wakes every timer that is due, returning how many were woken and
+    /// when the next one is due
+    pub(crate) fn fire_expired(&self) -> (usize, Option<Instant>) {
+        let now = self.now();
+        let mut state = self.state.lock().unwrap();
+        let later = state.timers.split_off(&(now, u64::MAX));
+        let expired = std::mem::replace(&mut state.timers, later);
+        let next = state.timers.keys().next().map(|&(deadline, _)| deadline);
+        drop(state);
+
+        // Woken outside the lock, waking may register new timers
+        let count = expired.len();
+        for waker in expired.into_values() {
+            waker.wake();
+        }
+        (count, next)
+    }
+
+    fn register(&self, deadline: Instant, waker: &Waker) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.timers.insert((deadline, id), waker.clone());
+        id
+    }
+
+    /// Replaces the waker, returning false if the timer has already fired
+    fn update(&self, key: (Instant, u64), waker: &Waker) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.timers.get_mut(&key) {
+            Some(registered) => {
+                if !registered.will_wake(waker) {
+                    *registered = waker.clone();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&self, key: (Instant, u64)) {
+        self.state.lock().unwrap().timers.remove(&key);
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().timers.len()
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+fn current() -> Arc<Driver> {
+    CURRENT.with(|current| current.borrow().clone()).expect("timers must be polled on an executor")
+}
+
+/// The time on the clock of the executor running this task, which is the
+/// real time unless it's a simulation
+pub fn now() -> Instant {
+    let driver = CURRENT.with(|current| current.borrow().clone());
+    driver.map_or_else(Instant::now, |driver| driver.now())
+}
+
+/// How many timers are registered with the executor running this task
+pub fn timers_pending() -> usize {
+    current().len()
+}
+
+/// Completes once `duration` has passed
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(now() + duration)
+}
+
+/// Completes once `deadline` has passed
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        registration: None,
+    }
+}
+
+/// Ticks every `period`, starting straight away
+pub fn interval(period: Duration) -> Interval {
+    assert!(period > Duration::from_secs(0), "an interval's period must be more than zero");
+    Interval {
+        sleep: sleep_until(now()),
+        period,
+    }
+}
+
+/// Runs `future`, giving up on it if it takes longer than `duration`
+pub fn timeout<F: Future>(future: F, duration: Duration) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+/// The future returned by `sleep` and `sleep_until`. Dropping it cancels
+/// the timer.
+pub struct Sleep {
+    deadline: Instant,
+    /// The driver the timer is registered with, and its key there
+    registration: Option<(Arc<Driver>, u64)>,
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Moves the deadline, as if the sleep had been created with it
+    pub fn reset(&mut self, deadline: Instant) {
+        self.cancel();
+        self.deadline = deadline;
+    }
+
+    fn cancel(&mut self) {
+        if let Some((driver, id)) = self.registration.take() {
+            driver.remove((self.deadline, id));
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        futures::ready!(coop::poll_proceed(cx));
+        if now() >= self.deadline {
+            self.cancel();
+            return Poll::Ready(());
+        }
+
+        // Not registered yet, or already fired by a driver that saw the
+        // deadline pass a moment before this poll did
+        let deadline = self.deadline;
+        let registered = match self.registration {
+            Some((ref driver, id)) => driver.update((deadline, id), cx.waker()),
+            None => false,
+        };
+        if !registered {
+            let driver = self.registration.take().map_or_else(current, |(driver, _)| driver);
+            let id = driver.register(deadline, cx.waker());
+            self.registration = Some((driver, id));
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Ticks at a fixed period. If ticks are missed because the task was busy,
+/// the next tick is a period after the late one rather than a burst to
+/// catch up. It's also a `Stream` of the ticks, which never ends.
+pub struct Interval {
+    sleep: Sleep,
+    period: Duration,
+}
+
+impl Interval {
+    /// Completes at the next tick, with the time it was due
+    pub async fn tick(&mut self) -> Instant {
+        futures::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    pub fn poll_tick(&mut self, cx: &mut Context) -> Poll<Instant> {
+        match Pin::new(&mut self.sleep).poll(cx) {
+            Poll::Ready(()) => {
+                let due = self.sleep.deadline();
+                let next = (due + self.period).max(now());
+                self.sleep.reset(next);
+                Poll::Ready(due)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Instant>> {
+        self.poll_tick(cx).map(Some)
+    }
+}
+
+/// The future returned by `timeout`
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+/// The error from a `Timeout` that ran out of time
+#[derive(Debug, PartialEq)]
+pub struct Elapsed;
+
+impl<F> Timeout<F> {
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // SAFETY: `future` is pinned whenever `self` is: it's never moved
+        // out of a pinned `Timeout`, `into_inner` takes `self` by value so
+        // needs it unpinned, and there's no `Drop` impl that could move it.
+        // `sleep` is `Unpin`, so it needs no such care.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
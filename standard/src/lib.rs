@@ -1,3 +1,5 @@
+pub mod executor;
+
 pub trait Payable {
     fn name(&self) -> &str;
     fn iban(&self) -> &str;
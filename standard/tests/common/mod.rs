@@ -0,0 +1,29 @@
+//! Helpers shared by the executor tests
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use standard::executor::{Executor, Spawner};
+
+/// Runs `make_future` as a task on a fresh executor and returns what it
+/// resolves to
+pub fn run_with_spawner<F, T>(threads: usize, make_future: impl FnOnce(Spawner) -> F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let executor = Executor::new(threads);
+    let spawner = executor.spawner();
+    let output = Arc::new(Mutex::new(None));
+    let task_output = output.clone();
+    let future = make_future(spawner.clone());
+    spawner.spawn(async move {
+        *task_output.lock().unwrap() = Some(future.await);
+    });
+    drop(spawner);
+    executor.run();
+
+    let output = output.lock().unwrap().take();
+    output.expect("the task didn't finish")
+}
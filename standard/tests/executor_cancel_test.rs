@@ -0,0 +1,235 @@
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use standard::executor::{time, Executor};
+use self::common::run_with_spawner;
+
+/// Counts how many of the futures it's attached to have been created and
+/// dropped, to check that none are leaked
+#[derive(Clone, Default)]
+struct DropCounter {
+    created: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+}
+
+struct Counted {
+    dropped: Arc<AtomicUsize>,
+}
+
+impl DropCounter {
+    fn track(&self) -> Counted {
+        self.created.fetch_add(1, Ordering::SeqCst);
+        Counted {
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    fn assert_none_leaked(&self) {
+        let created = self.created.load(Ordering::SeqCst);
+        assert!(created > 0);
+        assert_eq!(self.dropped.load(Ordering::SeqCst), created);
+    }
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_abort_cancels_a_running_task() {
+    let counter = DropCounter::default();
+    let task_counter = counter.clone();
+
+    let result = run_with_spawner(3, |spawner| async move {
+        let handle = spawner.spawn(async move {
+            let _counted = task_counter.track();
+            time::sleep(Duration::from_secs(60)).await;
+        });
+        time::sleep(Duration::from_millis(10)).await;
+        handle.abort();
+        handle.await
+    });
+
+    assert!(result.unwrap_err().is_cancelled());
+    counter.assert_none_leaked();
+}
+
+#[test]
+fn test_abort_before_the_first_poll() {
+    let counter = DropCounter::default();
+    let task_counter = counter.clone();
+
+    let result = run_with_spawner(3, |spawner| async move {
+        let counted = task_counter.track();
+        let handle = spawner.spawn(async move {
+            let _counted = counted;
+            panic!("shouldn't have been polled");
+        });
+        handle.abort();
+        handle.await
+    });
+
+    assert!(result.unwrap_err().is_cancelled());
+    counter.assert_none_leaked();
+}
+
+#[test]
+fn test_abort_after_finishing_keeps_the_output() {
+    let result = run_with_spawner(3, |spawner| async move {
+        let handle = spawner.spawn(async { 5 });
+        while !handle.is_finished() {
+            time::sleep(Duration::from_millis(1)).await;
+        }
+        handle.abort();
+        handle.await
+    });
+
+    assert_eq!(result.unwrap(), 5);
+}
+
+#[test]
+fn test_abort_handle_outlives_the_join_handle() {
+    let counter = DropCounter::default();
+    let task_counter = counter.clone();
+    let executor = Executor::new(2);
+    let spawner = executor.spawner();
+
+    let abort = spawner
+        .spawn(async move {
+            let _counted = task_counter.track();
+            futures::future::pending::<()>().await;
+        })
+        .abort_handle();
+    spawner.spawn(async move {
+        time::sleep(Duration::from_millis(10)).await;
+        abort.abort();
+    });
+    drop(spawner);
+    // Would hang if the pending task were still running
+    executor.run();
+
+    counter.assert_none_leaked();
+}
+
+#[test]
+fn test_scope_waits_for_its_children() {
+    let finished = Arc::new(AtomicUsize::new(0));
+    let task_finished = finished.clone();
+
+    let children_done = run_with_spawner(3, |spawner| async move {
+        let children_finished = task_finished.clone();
+        spawner
+            .scope(|scope| async move {
+                for n in 0..10 {
+                    let finished = children_finished.clone();
+                    scope.spawn(async move {
+                        time::sleep(Duration::from_millis(n * 3)).await;
+                        finished.fetch_add(1, Ordering::SeqCst);
+                    });
+                }
+            })
+            .await;
+        task_finished.load(Ordering::SeqCst)
+    });
+
+    assert_eq!(children_done, 10);
+    assert_eq!(finished.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn test_children_can_spawn_more_children() {
+    let finished = Arc::new(AtomicUsize::new(0));
+    let task_finished = finished.clone();
+
+    run_with_spawner(3, |spawner| async move {
+        spawner
+            .scope(|scope| async move {
+                let inner_scope = scope.clone();
+                scope.spawn(async move {
+                    time::sleep(Duration::from_millis(5)).await;
+                    inner_scope.spawn(async move {
+                        time::sleep(Duration::from_millis(5)).await;
+                        task_finished.fetch_add(1, Ordering::SeqCst);
+                    });
+                });
+            })
+            .await;
+    });
+
+    assert_eq!(finished.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_cancelling_a_scope_aborts_its_children() {
+    let counter = DropCounter::default();
+    let task_counter = counter.clone();
+
+    let results = run_with_spawner(3, |spawner| async move {
+        spawner
+            .scope(|scope| async move {
+                let handles: Vec<_> = (0..20)
+                    .map(|_| {
+                        let counted = task_counter.track();
+                        scope.spawn(async move {
+                            let _counted = counted;
+                            futures::future::pending::<()>().await;
+                        })
+                    })
+                    .collect();
+                scope.cancel();
+                futures::future::join_all(handles).await
+            })
+            .await
+    });
+
+    assert!(results.iter().all(|result| result.as_ref().unwrap_err().is_cancelled()));
+    counter.assert_none_leaked();
+}
+
+#[test]
+fn test_dropping_the_scope_future_aborts_its_children() {
+    let counter = DropCounter::default();
+    let task_counter = counter.clone();
+
+    let timed_out = run_with_spawner(3, |spawner| async move {
+        let scope = spawner.scope(|scope| async move {
+            for _ in 0..20 {
+                let counted = task_counter.track();
+                scope.spawn(async move {
+                    let _counted = counted;
+                    time::sleep(Duration::from_secs(60)).await;
+                });
+            }
+        });
+        time::timeout(scope, Duration::from_millis(20)).await.is_err()
+    });
+
+    assert!(timed_out);
+    // The executor only returns once the aborted children are dropped
+    counter.assert_none_leaked();
+}
+
+#[test]
+fn test_a_panicking_child_still_ends_the_scope() {
+    let result = run_with_spawner(3, |spawner| async move {
+        spawner
+            .scope(|scope| async move {
+                let handle = scope.spawn(async {
+                    time::sleep(Duration::from_millis(5)).await;
+                    panic!("child failed");
+                });
+                handle.await
+            })
+            .await
+    });
+
+    assert!(result.unwrap_err().is_panic());
+}
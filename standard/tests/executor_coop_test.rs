@@ -0,0 +1,147 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use standard::executor::{sync::Semaphore, yield_now, Executor, Priority};
+
+#[test]
+fn test_spawners_have_a_priority() {
+    let executor = Executor::new(1);
+    let spawner = executor.spawner();
+    assert_eq!(spawner.priority(), Priority::Normal);
+
+    let high = spawner.with_priority(Priority::High);
+    assert_eq!(high.priority(), Priority::High);
+    assert_eq!(high.clone().priority(), Priority::High);
+    assert_eq!(spawner.priority(), Priority::Normal);
+}
+
+#[test]
+fn test_yield_now_lets_the_other_tasks_run() {
+    let executor = Executor::new(1);
+    let spawner = executor.spawner();
+    let trace = Arc::new(Mutex::new(Vec::new()));
+    for name in &["a", "b"] {
+        let trace = trace.clone();
+        spawner.spawn(async move {
+            for _ in 0..3 {
+                trace.lock().unwrap().push(*name);
+                yield_now().await;
+            }
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    assert_eq!(*trace.lock().unwrap(), vec!["a", "b", "a", "b", "a", "b"]);
+}
+
+#[test]
+fn test_high_priority_tasks_are_not_starved_by_a_flood_of_low_ones() {
+    let executor = Executor::new(1);
+    let spawner = executor.spawner();
+    let low_polls = Arc::new(AtomicUsize::new(0));
+    for _ in 0..200 {
+        let low_polls = low_polls.clone();
+        spawner.with_priority(Priority::Low).spawn(async move {
+            for _ in 0..100 {
+                low_polls.fetch_add(1, Ordering::SeqCst);
+                yield_now().await;
+            }
+        });
+    }
+    let high = {
+        let low_polls = low_polls.clone();
+        spawner.with_priority(Priority::High).spawn(async move {
+            for _ in 0..20 {
+                yield_now().await;
+            }
+            low_polls.load(Ordering::SeqCst)
+        })
+    };
+    let low_polls_before_high_finished = Arc::new(Mutex::new(None));
+    {
+        let result = low_polls_before_high_finished.clone();
+        spawner.spawn(async move {
+            *result.lock().unwrap() = Some(high.await.unwrap());
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    // Taking turns with all of them would be 200 for each of the 20 yields
+    let low_polls_before_high_finished = low_polls_before_high_finished.lock().unwrap().unwrap();
+    assert!(low_polls_before_high_finished < 200, "{}", low_polls_before_high_finished);
+    assert_eq!(low_polls.load(Ordering::SeqCst), 200 * 100);
+}
+
+#[test]
+fn test_low_priority_tasks_still_get_a_turn() {
+    let executor = Executor::new(1);
+    let spawner = executor.spawner();
+    let low_done = Arc::new(AtomicBool::new(false));
+    for _ in 0..4 {
+        let low_done = low_done.clone();
+        spawner.with_priority(Priority::High).spawn(async move {
+            while !low_done.load(Ordering::SeqCst) {
+                yield_now().await;
+            }
+        });
+    }
+    {
+        let low_done = low_done.clone();
+        spawner.with_priority(Priority::Low).spawn(async move {
+            for _ in 0..10 {
+                yield_now().await;
+            }
+            low_done.store(true, Ordering::SeqCst);
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    assert!(low_done.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_a_task_that_is_always_ready_yields_when_its_budget_runs_out() {
+    let executor = Executor::new(1).poll_budget(8);
+    let spawner = executor.spawner();
+    let stop = Arc::new(AtomicBool::new(false));
+    let acquired = Arc::new(AtomicUsize::new(0));
+    {
+        let stop = stop.clone();
+        let acquired = acquired.clone();
+        spawner.spawn(async move {
+            // The permit is always free, so this never waits
+            let semaphore = Semaphore::new(1);
+            while !stop.load(Ordering::SeqCst) {
+                drop(semaphore.acquire().await.unwrap());
+                acquired.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+    }
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    {
+        let seen = seen.clone();
+        spawner.spawn(async move {
+            for _ in 0..10 {
+                seen.lock().unwrap().push(acquired.load(Ordering::SeqCst));
+                yield_now().await;
+            }
+            stop.store(true, Ordering::SeqCst);
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    let seen = seen.lock().unwrap();
+    for pair in seen.windows(2) {
+        assert!(pair[1] - pair[0] <= 8, "{:?}", seen);
+    }
+    assert!(seen[seen.len() - 1] > 0);
+}
@@ -0,0 +1,144 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use futures::io::{AsyncReadExt, AsyncWriteExt};
+use standard::executor::{
+    local::{block_on, spawn_local, LocalExecutor},
+    net::{AsyncTcpListener, AsyncTcpStream},
+    time, Executor,
+};
+
+#[test]
+fn test_block_on_returns_the_output() {
+    assert_eq!(block_on(async { 40 + 2 }), 42);
+}
+
+#[test]
+fn test_block_on_is_woken_from_another_thread() {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let sending = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        sender.send("hello").unwrap();
+    });
+
+    assert_eq!(block_on(receiver), Ok("hello"));
+    sending.join().unwrap();
+}
+
+#[test]
+fn test_block_on_can_spawn_tasks() {
+    let sum = block_on(async {
+        let handles: Vec<_> = (0..10u64).map(|n| spawn_local(async move { n * n })).collect();
+        let mut sum = 0;
+        for handle in handles {
+            sum += handle.await.unwrap();
+        }
+        sum
+    });
+
+    assert_eq!(sum, 285);
+}
+
+#[test]
+fn test_tasks_need_not_be_send() {
+    let executor = LocalExecutor::new();
+    let spawner = executor.spawner();
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    for n in 0..3 {
+        let log = log.clone();
+        spawner.spawn(async move {
+            time::sleep(Duration::from_millis(10 * (3 - n))).await;
+            log.borrow_mut().push(n);
+        });
+    }
+    let inner_log = log.clone();
+    let task = spawner.spawn(async move { inner_log.borrow().len() });
+    executor.block_on(time::sleep(Duration::from_millis(50)));
+
+    assert_eq!(*log.borrow(), [2, 1, 0]);
+    assert_eq!(executor.block_on(task).unwrap(), 0);
+}
+
+#[test]
+fn test_unfinished_tasks_carry_on_at_the_next_block_on() {
+    let executor = LocalExecutor::new();
+    let finished = Rc::new(RefCell::new(false));
+
+    let task_finished = finished.clone();
+    let handle = executor.spawner().spawn(async move {
+        time::sleep(Duration::from_millis(20)).await;
+        *task_finished.borrow_mut() = true;
+    });
+    executor.block_on(async {});
+    assert!(!*finished.borrow());
+
+    executor.block_on(handle).unwrap();
+    assert!(*finished.borrow());
+}
+
+#[test]
+fn test_dropping_the_executor_cancels_its_tasks() {
+    let executor = LocalExecutor::new();
+    let handle = executor.spawner().spawn(futures::future::pending::<()>());
+    executor.block_on(async {});
+    drop(executor);
+
+    let err = block_on(handle).unwrap_err();
+    assert!(err.is_cancelled());
+}
+
+#[test]
+fn test_block_on_waits_for_timers_without_spinning() {
+    let start = Instant::now();
+    block_on(time::sleep(Duration::from_millis(30)));
+
+    assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[test]
+fn test_block_on_drives_sockets() {
+    let echoed = block_on(async {
+        let mut listener = AsyncTcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        spawn_local(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 5];
+            socket.read_exact(&mut buf).await.unwrap();
+            socket.write_all(&buf).await.unwrap();
+        });
+
+        let mut stream = AsyncTcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        buf
+    });
+
+    assert_eq!(echoed, b"hello");
+}
+
+#[test]
+fn test_block_on_inside_a_task_of_another_executor() {
+    let executor = Executor::new(2);
+    let output = Arc::new(Mutex::new(None));
+
+    let task_output = output.clone();
+    executor.spawner().spawn(async move {
+        time::sleep(Duration::from_millis(5)).await;
+        let inner = block_on(async {
+            time::sleep(Duration::from_millis(5)).await;
+            spawn_local(async { 7 }).await.unwrap()
+        });
+        // Back on the executor's own timers
+        time::sleep(Duration::from_millis(5)).await;
+        *task_output.lock().unwrap() = Some(inner);
+    });
+    executor.run();
+
+    assert_eq!(*output.lock().unwrap(), Some(7));
+}
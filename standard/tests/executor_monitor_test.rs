@@ -0,0 +1,199 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use standard::executor::{
+    sync::{oneshot, Notify},
+    yield_now, Executor, Monitor, Priority, TaskInfo, TaskState,
+};
+
+fn find(monitor: &Monitor, name: &str) -> TaskInfo {
+    let tasks = monitor.tasks();
+    tasks.into_iter().find(|task| task.name.as_deref() == Some(name)).unwrap()
+}
+
+#[test]
+fn test_tasks_lists_the_tasks_that_have_not_finished() {
+    let executor = Executor::new(1);
+    let monitor = executor.monitor();
+    let spawner = executor.spawner();
+    let (sender, receiver) = oneshot::channel::<()>();
+    spawner.spawn_named("waiting", async move {
+        receiver.await.unwrap();
+    });
+    let tasks = Arc::new(Mutex::new(Vec::new()));
+    {
+        let monitor = spawner.monitor();
+        let tasks = tasks.clone();
+        spawner.with_priority(Priority::Low).spawn(async move {
+            yield_now().await;
+            *tasks.lock().unwrap() = monitor.tasks();
+            sender.send(()).unwrap();
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    let tasks = tasks.lock().unwrap();
+    assert_eq!(tasks.len(), 2);
+    let (waiting, looking) = (&tasks[0], &tasks[1]);
+    assert!(waiting.id < looking.id);
+
+    assert_eq!(waiting.name.as_deref(), Some("waiting"));
+    assert_eq!(waiting.state, TaskState::Idle);
+    assert_eq!(waiting.priority, Priority::Normal);
+    assert_eq!(waiting.polls, 1);
+    assert!(waiting.location.file().ends_with("executor_monitor_test.rs"));
+
+    assert_eq!(looking.name, None);
+    assert_eq!(looking.state, TaskState::Running);
+    assert_eq!(looking.priority, Priority::Low);
+    // The poll it's in isn't counted until it's over
+    assert_eq!(looking.polls, 1);
+
+    assert!(monitor.tasks().is_empty());
+}
+
+#[test]
+fn test_a_woken_task_is_scheduled_until_it_is_polled() {
+    let executor = Executor::new(1);
+    let spawner = executor.spawner();
+    let notify = Arc::new(Notify::new());
+    let states = Arc::new(Mutex::new(Vec::new()));
+    {
+        let notify = notify.clone();
+        spawner.spawn_named("notified", async move { notify.notified().await });
+    }
+    {
+        let monitor = spawner.monitor();
+        let states = states.clone();
+        spawner.spawn(async move {
+            yield_now().await;
+            states.lock().unwrap().push(find(&monitor, "notified").state);
+            notify.notify_one();
+            states.lock().unwrap().push(find(&monitor, "notified").state);
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    assert_eq!(*states.lock().unwrap(), vec![TaskState::Idle, TaskState::Scheduled]);
+}
+
+#[test]
+fn test_polls_are_counted_and_timed() {
+    let executor = Executor::new(1);
+    let spawner = executor.spawner();
+    let seen = Arc::new(Mutex::new(None));
+    {
+        let monitor = spawner.monitor();
+        let seen = seen.clone();
+        spawner.spawn_named("busy", async move {
+            for _ in 0..5 {
+                thread::sleep(Duration::from_millis(2));
+                yield_now().await;
+            }
+            *seen.lock().unwrap() = Some(find(&monitor, "busy"));
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    let seen = seen.lock().unwrap().take().unwrap();
+    assert_eq!(seen.polls, 5);
+    assert!(seen.poll_time >= Duration::from_millis(10), "{:?}", seen.poll_time);
+}
+
+#[test]
+fn test_aborted_and_dropped_tasks_are_not_listed() {
+    let executor = Executor::new(1);
+    let monitor = executor.monitor();
+    let spawner = executor.spawner();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    {
+        let monitor = spawner.monitor();
+        let inner = spawner.clone();
+        let seen = seen.clone();
+        spawner.spawn(async move {
+            let (_sender, receiver) = oneshot::channel::<()>();
+            let aborted = inner.spawn_named("aborted", receiver);
+            for _ in 0..3 {
+                yield_now().await;
+            }
+            seen.lock().unwrap().push(monitor.tasks().len());
+            aborted.abort();
+            assert!(aborted.await.unwrap_err().is_cancelled());
+            seen.lock().unwrap().push(monitor.tasks().len());
+        });
+    }
+    // Nothing can ever wake it, so it's dropped
+    spawner.spawn_named("forgotten", futures::future::pending::<()>());
+    drop(spawner);
+
+    executor.run();
+
+    assert_eq!(*seen.lock().unwrap(), vec![2, 1]);
+    assert!(monitor.tasks().is_empty());
+}
+
+#[test]
+fn test_slow_polls_are_reported() {
+    let slow = Arc::new(Mutex::new(Vec::new()));
+    let executor = {
+        let slow = slow.clone();
+        Executor::new(1)
+            .slow_poll_threshold(Duration::from_millis(20))
+            .on_slow_poll(move |task, took| slow.lock().unwrap().push((task.clone(), took)))
+    };
+    let spawner = executor.spawner();
+    spawner.spawn_named("quick", yield_now());
+    spawner.spawn_named("slow", async {
+        yield_now().await;
+        thread::sleep(Duration::from_millis(40));
+    });
+    drop(spawner);
+
+    executor.run();
+
+    let slow = slow.lock().unwrap();
+    assert_eq!(slow.len(), 1);
+    let (ref task, took) = slow[0];
+    assert_eq!(task.name.as_deref(), Some("slow"));
+    assert_eq!(task.polls, 2);
+    assert!(took >= Duration::from_millis(40));
+}
+
+#[test]
+fn test_the_dump_has_a_line_for_each_task() {
+    let executor = Executor::new(1);
+    let monitor = executor.monitor();
+    let spawner = executor.spawner();
+    let (sender, receiver) = oneshot::channel::<()>();
+    spawner.spawn_named("waiting", async move {
+        receiver.await.unwrap();
+    });
+    let dump = Arc::new(Mutex::new(String::new()));
+    {
+        let dump = dump.clone();
+        spawner.spawn(async move {
+            yield_now().await;
+            *dump.lock().unwrap() = monitor.to_string();
+            sender.send(()).unwrap();
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    let dump = dump.lock().unwrap();
+    let lines: Vec<_> = dump.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", dump);
+    assert_eq!(lines[0], "2 tasks");
+    assert!(lines[1].starts_with("  task 0 \"waiting\" spawned at "), "{}", dump);
+    assert!(lines[1].contains(": idle, Normal priority, polled 1 times"), "{}", dump);
+    assert!(lines[2].contains(": running,"), "{}", dump);
+}
@@ -0,0 +1,114 @@
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use futures::io::{AsyncReadExt, AsyncWriteExt};
+use standard::executor::{
+    net::{AsyncTcpListener, AsyncTcpStream},
+    time, Executor, Spawner,
+};
+
+/// Echoes everything a client sends until it closes its side
+async fn echo(mut socket: AsyncTcpStream) {
+    let mut buf = vec![0; 1024];
+    loop {
+        match socket.read(&mut buf).await.unwrap() {
+            0 => break,
+            n => socket.write_all(&buf[..n]).await.unwrap(),
+        }
+    }
+}
+
+/// Accepts `connections` clients and echoes for each of them
+fn serve(spawner: &Spawner, mut listener: AsyncTcpListener, connections: usize) {
+    let inner_spawner = spawner.clone();
+    spawner.spawn(async move {
+        for _ in 0..connections {
+            let (socket, _) = listener.accept().await.unwrap();
+            inner_spawner.spawn(echo(socket));
+        }
+    });
+}
+
+#[test]
+fn test_echo_round_trip_with_a_blocking_client() {
+    let listener = AsyncTcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let executor = Executor::new(2);
+    serve(&executor.spawner(), listener, 1);
+
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(b"hello\n").unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut echoed = Vec::new();
+        stream.read_to_end(&mut echoed).unwrap();
+        echoed
+    });
+    executor.run();
+
+    assert_eq!(client.join().unwrap(), b"hello\n");
+}
+
+#[test]
+fn test_many_clients_on_the_same_executor() {
+    const CLIENTS: usize = 50;
+    const MESSAGE_SIZE: usize = 64 * 1024;
+
+    let listener = AsyncTcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let executor = Executor::new(3);
+    let spawner = executor.spawner();
+    serve(&spawner, listener, CLIENTS);
+
+    let echoed = Arc::new(Mutex::new(Vec::new()));
+    for n in 0..CLIENTS {
+        let echoed = echoed.clone();
+        spawner.spawn(async move {
+            let message: Vec<u8> = (0..MESSAGE_SIZE).map(|i| (i + n) as u8).collect();
+            let stream = AsyncTcpStream::connect(addr).await.unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), addr);
+
+            // Large enough not to fit in the socket buffers, so it has to
+            // be read back while it's still being written
+            let (mut reader, mut writer) = stream.split();
+            let write = async {
+                writer.write_all(&message).await.unwrap();
+                writer.close().await.unwrap();
+            };
+            let mut buf = Vec::new();
+            let read = reader.read_to_end(&mut buf);
+            let (_, read) = futures::join!(write, read);
+            read.unwrap();
+
+            assert!(buf == message, "client {} got back something else", n);
+            echoed.lock().unwrap().push(n);
+        });
+    }
+    drop(spawner);
+    executor.run();
+
+    assert_eq!(echoed.lock().unwrap().len(), CLIENTS);
+}
+
+#[test]
+fn test_connecting_to_a_closed_port_fails() {
+    let addr = AsyncTcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let executor = Executor::new(1);
+    let result = Arc::new(Mutex::new(None));
+
+    let task_result = result.clone();
+    executor.spawner().spawn(async move {
+        let connect = AsyncTcpStream::connect(addr);
+        let result = time::timeout(connect, Duration::from_secs(5)).await.unwrap();
+        *task_result.lock().unwrap() = Some(result.map(|_| ()));
+    });
+    executor.run();
+
+    let err = result.lock().unwrap().take().unwrap().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::ConnectionRefused);
+}
@@ -0,0 +1,144 @@
+use std::{
+    env,
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use standard::executor::{time, Executor};
+
+/// Set when the test binary runs itself to check that it aborts
+const ABORT_CHILD: &str = "EXECUTOR_PANIC_TEST_ABORT_CHILD";
+
+/// Panics when it's dropped
+struct PanicOnDrop;
+
+impl Drop for PanicOnDrop {
+    fn drop(&mut self) {
+        panic!("dropped");
+    }
+}
+
+#[test]
+fn test_other_tasks_keep_running_after_a_panic() {
+    for &threads in &[1, 4] {
+        let executor = Executor::new(threads);
+        let spawner = executor.spawner();
+        let completed = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for n in 0..100 {
+            let completed = completed.clone();
+            handles.push(spawner.spawn(async move {
+                time::sleep(Duration::from_millis(n % 5)).await;
+                if n % 10 == 0 {
+                    panic!("task {} failed", n);
+                }
+                completed.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        let panicked = Arc::new(AtomicUsize::new(0));
+        let task_panicked = panicked.clone();
+        spawner.spawn(async move {
+            for handle in handles {
+                if handle.await.is_err() {
+                    task_panicked.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+        drop(spawner);
+        executor.run();
+
+        assert_eq!(completed.load(Ordering::SeqCst), 90, "with {} threads", threads);
+        assert_eq!(panicked.load(Ordering::SeqCst), 10, "with {} threads", threads);
+    }
+}
+
+#[test]
+fn test_panic_hook_sees_every_panic() {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let hook_messages = messages.clone();
+    let executor = Executor::new(2).on_panic(move |err| {
+        assert!(err.is_panic());
+        hook_messages.lock().unwrap().push(err.to_string());
+    });
+    let spawner = executor.spawner();
+
+    // Detached, so the hook is the only place to hear about it
+    spawner.spawn(async { panic!("detached") });
+    let handle = spawner.spawn(async {
+        time::sleep(Duration::from_millis(5)).await;
+        panic!("joined")
+    });
+    spawner.spawn(async move {
+        assert!(handle.await.unwrap_err().is_panic());
+    });
+    drop(spawner);
+    executor.run();
+
+    let mut messages = messages.lock().unwrap().clone();
+    messages.sort();
+    assert_eq!(messages, ["task panicked: detached", "task panicked: joined"]);
+}
+
+#[test]
+fn test_panic_while_dropping_an_unfinished_future_is_caught() {
+    let hook_calls = Arc::new(AtomicUsize::new(0));
+    let task_hook_calls = hook_calls.clone();
+    let completed = Arc::new(AtomicUsize::new(0));
+    let executor = Executor::new(1).on_panic(move |_| {
+        task_hook_calls.fetch_add(1, Ordering::SeqCst);
+    });
+    let spawner = executor.spawner();
+
+    // Dropped outside of any poll of their own: one because nothing can
+    // wake it, the other because it's aborted
+    spawner.spawn(async {
+        let _bomb = PanicOnDrop;
+        futures::future::pending::<()>().await;
+    });
+    let bomb = spawner.spawn(async {
+        let _bomb = PanicOnDrop;
+        time::sleep(Duration::from_secs(60)).await;
+    });
+    for _ in 0..10 {
+        let completed = completed.clone();
+        spawner.spawn(async move {
+            time::sleep(Duration::from_millis(5)).await;
+            completed.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    spawner.spawn(async move {
+        time::sleep(Duration::from_millis(1)).await;
+        bomb.abort();
+        assert!(bomb.await.unwrap_err().is_cancelled());
+    });
+    drop(spawner);
+    executor.run();
+
+    assert_eq!(hook_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(completed.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn test_abort_on_panic() {
+    if env::var_os(ABORT_CHILD).is_some() {
+        let executor = Executor::new(2).abort_on_panic();
+        executor.spawner().spawn(async { panic!("fatal") });
+        executor.run();
+        // Only reached if it didn't abort
+        std::process::exit(0);
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["test_abort_on_panic", "--exact", "--nocapture"])
+        .env(ABORT_CHILD, "1")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("aborting, task panicked: fatal"), "stderr: {}", stderr);
+}
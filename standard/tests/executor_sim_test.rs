@@ -0,0 +1,203 @@
+use std::{
+    cell::{Cell, RefCell},
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use standard::executor::{
+    sim::{self, Simulation},
+    sync::Mutex,
+    time, yield_now,
+};
+
+/// Which task ran at each step, for a few tasks that keep yielding
+fn interleaving(seed: u64) -> Vec<usize> {
+    let simulation = Simulation::new(seed);
+    let spawner = simulation.spawner();
+    let trace = Rc::new(RefCell::new(Vec::new()));
+    for task in 0..4 {
+        let trace = trace.clone();
+        spawner.spawn(async move {
+            for _ in 0..5 {
+                trace.borrow_mut().push(task);
+                yield_now().await;
+            }
+        });
+    }
+    simulation.run();
+    let trace = trace.borrow().clone();
+    trace
+}
+
+#[test]
+fn test_sleeping_takes_no_real_time() {
+    let simulation = Simulation::new(0);
+    let started = Instant::now();
+
+    simulation.block_on(async {
+        time::sleep(Duration::from_secs(60 * 60)).await;
+    });
+
+    assert_eq!(simulation.elapsed(), Duration::from_secs(60 * 60));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_the_clock_stands_still_while_tasks_are_ready() {
+    let simulation = Simulation::new(0);
+
+    simulation.block_on(async {
+        let before = time::now();
+        for _ in 0..100 {
+            yield_now().await;
+        }
+        assert_eq!(time::now(), before);
+    });
+
+    assert_eq!(simulation.elapsed(), Duration::from_secs(0));
+}
+
+#[test]
+fn test_timers_fire_in_deadline_order() {
+    let simulation = Simulation::new(0);
+    let spawner = simulation.spawner();
+    let fired = Rc::new(RefCell::new(Vec::new()));
+    for &secs in &[3, 1, 4, 1, 5] {
+        let fired = fired.clone();
+        spawner.spawn(async move {
+            time::sleep(Duration::from_secs(secs)).await;
+            fired.borrow_mut().push((secs, time::now()));
+        });
+    }
+
+    simulation.run();
+
+    let fired = fired.borrow();
+    let order: Vec<_> = fired.iter().map(|&(secs, _)| secs).collect();
+    assert_eq!(order, vec![1, 1, 3, 4, 5]);
+    // Each woke at exactly its deadline
+    let start = simulation.now() - Duration::from_secs(5);
+    for &(secs, woke) in fired.iter() {
+        assert_eq!(woke - start, Duration::from_secs(secs));
+    }
+}
+
+#[test]
+fn test_intervals_and_timeouts_use_the_virtual_clock() {
+    let simulation = Simulation::new(0);
+
+    let (ticks, timed_out) = simulation.block_on(async {
+        let start = time::now();
+        let mut interval = time::interval(Duration::from_millis(250));
+        let mut ticks = Vec::new();
+        for _ in 0..4 {
+            ticks.push(interval.tick().await - start);
+        }
+        let never = futures::future::pending::<()>();
+        let timed_out = time::timeout(never, Duration::from_secs(30)).await;
+        (ticks, timed_out)
+    });
+
+    let millis: Vec<_> = ticks.iter().map(Duration::as_millis).collect();
+    assert_eq!(millis, vec![0, 250, 500, 750]);
+    assert_eq!(timed_out, Err(time::Elapsed));
+    assert_eq!(simulation.elapsed(), Duration::from_millis(30_750));
+}
+
+#[test]
+fn test_a_seed_replays_the_same_interleaving() {
+    for seed in 0..20 {
+        assert_eq!(interleaving(seed), interleaving(seed));
+    }
+}
+
+#[test]
+fn test_seeds_give_different_interleavings() {
+    let mut seen: Vec<_> = (0..20).map(interleaving).collect();
+    seen.sort();
+    seen.dedup();
+    assert!(seen.len() > 10);
+}
+
+#[test]
+fn test_spawned_tasks_can_spawn() {
+    let simulation = Simulation::new(7);
+    let spawner = simulation.spawner();
+
+    let total = simulation.block_on(async move {
+        let inner = spawner.clone();
+        let outer = spawner.spawn(async move {
+            let handles: Vec<_> = (1..=10).map(|n| inner.spawn(async move { n })).collect();
+            let mut total = 0;
+            for handle in handles {
+                total += handle.await.unwrap();
+            }
+            total
+        });
+        outer.await.unwrap()
+    });
+
+    assert_eq!(total, 55);
+}
+
+/// A lost update that only some interleavings hit
+fn racy_counter(simulation: &Simulation) {
+    let spawner = simulation.spawner();
+    let counter = Rc::new(Cell::new(0));
+    for _ in 0..2 {
+        let counter = counter.clone();
+        spawner.spawn(async move {
+            let read = counter.get();
+            yield_now().await;
+            counter.set(read + 1);
+        });
+    }
+    simulation.run();
+    assert_eq!(counter.get(), 2);
+}
+
+#[test]
+fn test_a_failing_seed_fails_again() {
+    let failing = (0..100)
+        .find(|&seed| panic::catch_unwind(|| racy_counter(&Simulation::new(seed))).is_err())
+        .expect("no seed hit the race");
+
+    for _ in 0..5 {
+        let result = panic::catch_unwind(|| racy_counter(&Simulation::new(failing)));
+        assert!(result.is_err());
+    }
+}
+
+#[test]
+fn test_check_seeds_passes_on_the_failure() {
+    let result = panic::catch_unwind(|| sim::check_seeds(0..100, racy_counter));
+    assert!(result.is_err());
+
+    // Fixed with a lock, every seed passes
+    sim::check_seeds(0..100, |simulation| {
+        let spawner = simulation.spawner();
+        let counter = Rc::new(Mutex::new(0));
+        for _ in 0..2 {
+            let counter = counter.clone();
+            spawner.spawn(async move {
+                let mut value = counter.lock().await;
+                let read = *value;
+                yield_now().await;
+                *value = read + 1;
+            });
+        }
+        simulation.run();
+        assert_eq!(*simulation.block_on(counter.lock()), 2);
+    });
+}
+
+#[test]
+fn test_a_stuck_simulation_panics() {
+    let simulation = Simulation::new(3);
+    simulation.spawner().spawn(futures::future::pending::<()>());
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| simulation.run()));
+    let payload = result.unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(message.contains("seed 3 is stuck"), "{}", message);
+}
@@ -0,0 +1,161 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use futures::Stream;
+use standard::executor::{
+    sim::{self, Simulation},
+    stream::StreamAdapters,
+    sync::mpsc,
+    time, yield_now, Executor,
+};
+
+async fn collect<S: Stream + Unpin>(mut stream: S) -> Vec<S::Item> {
+    let mut items = Vec::new();
+    while let Some(item) = stream.next().await {
+        items.push(item);
+    }
+    items
+}
+
+#[test]
+fn test_a_receiver_is_a_stream() {
+    sim::check_seeds(0..20, |simulation| {
+        let (sender, receiver) = mpsc::channel(2);
+        simulation.spawner().spawn(async move {
+            for n in 0..5 {
+                sender.send(n).await.unwrap();
+                yield_now().await;
+            }
+        });
+
+        let received = simulation.block_on(collect(receiver));
+
+        assert_eq!(received, vec![0, 1, 2, 3, 4]);
+    });
+}
+
+#[test]
+fn test_map_and_filter() {
+    let simulation = Simulation::new(0);
+    let (sender, receiver) = mpsc::channel(10);
+    for n in 0..6 {
+        sender.try_send(n).unwrap();
+    }
+    drop(sender);
+
+    let stream = receiver.filter(|n| n % 2 == 0).map(|n| n * 10);
+    let received = simulation.block_on(collect(stream));
+
+    assert_eq!(received, vec![0, 20, 40]);
+}
+
+#[test]
+fn test_an_interval_is_a_stream() {
+    let simulation = Simulation::new(0);
+
+    let ticks = simulation.block_on(async {
+        let start = time::now();
+        let mut ticks = time::interval(Duration::from_secs(2)).map(|tick| tick - start);
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            seen.push(ticks.next().await.unwrap().as_secs());
+        }
+        seen
+    });
+
+    assert_eq!(ticks, vec![0, 2, 4]);
+}
+
+#[test]
+fn test_buffer_unordered_limits_concurrency() {
+    let simulation = Simulation::new(0);
+    let running = Rc::new(Cell::new(0));
+    let most_running = Rc::new(Cell::new(0));
+    let jobs = [4, 1, 2, 5, 9, 1].iter().enumerate().map(|(job, &secs)| {
+        let running = running.clone();
+        let most_running = most_running.clone();
+        async move {
+            running.set(running.get() + 1);
+            most_running.set(most_running.get().max(running.get()));
+            time::sleep(Duration::from_secs(secs)).await;
+            running.set(running.get() - 1);
+            job
+        }
+    });
+
+    let stream = futures::stream::iter(jobs).buffer_unordered(3);
+    let finished = simulation.block_on(collect(stream));
+
+    // Each job starts as soon as one ahead of it finishes
+    assert_eq!(finished, vec![1, 2, 0, 5, 3, 4]);
+    assert_eq!(most_running.get(), 3);
+    assert_eq!(simulation.elapsed(), Duration::from_secs(11));
+}
+
+#[test]
+fn test_chunks_are_yielded_when_full_late_or_last() {
+    // However the sender and receiver interleave
+    sim::check_seeds(0..20, |simulation| {
+        let (sender, receiver) = mpsc::channel(10);
+        simulation.spawner().spawn(async move {
+            for n in 1..=4 {
+                sender.send(n).await.unwrap();
+            }
+            time::sleep(Duration::from_secs(15)).await;
+            sender.send(5).await.unwrap();
+            time::sleep(Duration::from_secs(1)).await;
+            sender.send(6).await.unwrap();
+        });
+
+        let start = simulation.now();
+        let chunks = simulation.block_on(async move {
+            let mut stream = receiver.chunks_timeout(3, Duration::from_secs(10));
+            let mut chunks = Vec::new();
+            while let Some(chunk) = stream.next().await {
+                chunks.push((chunk, (time::now() - start).as_secs()));
+            }
+            chunks
+        });
+
+        let expected = vec![(vec![1, 2, 3], 0), (vec![4], 10), (vec![5, 6], 16)];
+        assert_eq!(chunks, expected);
+    });
+}
+
+#[test]
+fn test_streams_on_the_executor() {
+    let executor = Executor::new(4);
+    let spawner = executor.spawner();
+    let (sender, receiver) = mpsc::channel(4);
+    for worker in 0..4 {
+        let sender = sender.clone();
+        spawner.spawn(async move {
+            for n in 0..25 {
+                sender.send(worker * 25 + n).await.unwrap();
+            }
+        });
+    }
+    drop(sender);
+    let total = Arc::new(Mutex::new(0));
+    {
+        let total = total.clone();
+        spawner.spawn(async move {
+            let squares = receiver.map(|n: u64| async move {
+                yield_now().await;
+                n * n
+            });
+            let mut squares = squares.buffer_unordered(8);
+            while let Some(square) = squares.next().await {
+                *total.lock().unwrap() += square;
+            }
+        });
+    }
+    drop(spawner);
+
+    executor.run();
+
+    assert_eq!(*total.lock().unwrap(), (0..100).map(|n| n * n).sum());
+}
@@ -0,0 +1,412 @@
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use futures::{future::BoxFuture, poll, FutureExt};
+use standard::executor::{
+    sync::{mpsc, oneshot, Mutex, Notify, RwLock, Semaphore, TryAcquireError},
+    yield_now, Executor,
+};
+
+/// Spawns a task, resolving once it's finished and passing on its panic
+type Spawn = Arc<dyn Fn(BoxFuture<'static, ()>) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Runs `test` on our executor and then on tokio's
+fn on_both_executors<F, Fut>(test: F)
+where
+    F: Fn(Spawn) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let executor = Executor::new(4);
+    let spawner = executor.spawner();
+    let task_spawner = spawner.clone();
+    let spawn: Spawn = Arc::new(move |future| {
+        let handle = task_spawner.spawn(future);
+        async move {
+            if let Err(err) = handle.await {
+                panic::resume_unwind(err.into_panic());
+            }
+        }
+        .boxed()
+    });
+    let handle = spawner.spawn(test(spawn));
+    let result = Arc::new(std::sync::Mutex::new(None));
+    let task_result = result.clone();
+    spawner.spawn(async move {
+        *task_result.lock().unwrap() = Some(handle.await);
+    });
+    drop(spawner);
+    executor.run();
+    let result = result.lock().unwrap().take().unwrap();
+    if let Err(err) = result {
+        panic!("on our executor: {}", err);
+    }
+
+    let mut runtime = tokio::runtime::Builder::new()
+        .threaded_scheduler()
+        .core_threads(4)
+        .build()
+        .unwrap();
+    let handle = runtime.handle().clone();
+    let spawn: Spawn = Arc::new(move |future| {
+        let handle = handle.spawn(future);
+        async move { handle.await.unwrap() }.boxed()
+    });
+    let result = panic::catch_unwind(AssertUnwindSafe(|| runtime.block_on(test(spawn))));
+    if result.is_err() {
+        panic!("on tokio");
+    }
+}
+
+#[test]
+fn test_mutex_keeps_updates_apart() {
+    on_both_executors(|spawn| async move {
+        let counter = Arc::new(Mutex::new(0));
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let counter = counter.clone();
+                spawn(
+                    async move {
+                        for _ in 0..50 {
+                            let mut value = counter.lock().await;
+                            let read = *value;
+                            // Anyone else getting in here would lose an update
+                            yield_now().await;
+                            *value = read + 1;
+                        }
+                    }
+                    .boxed(),
+                )
+            })
+            .collect();
+        futures::future::join_all(tasks).await;
+
+        assert_eq!(*counter.lock().await, 1_000);
+    });
+}
+
+#[test]
+fn test_mutex_is_handed_out_in_order() {
+    on_both_executors(|_| async {
+        let mutex = Mutex::new(());
+        let guard = mutex.lock().await;
+        let mut first = Box::pin(mutex.lock());
+        let mut second = Box::pin(mutex.lock());
+        assert!(poll!(&mut first).is_pending());
+        assert!(poll!(&mut second).is_pending());
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+
+        assert!(poll!(&mut second).is_pending());
+        let first = first.await;
+        assert!(poll!(&mut second).is_pending());
+        drop(first);
+        second.await;
+    });
+}
+
+#[test]
+fn test_dropped_mutex_waiters_give_up_their_place() {
+    on_both_executors(|_| async {
+        let mutex = Mutex::new(());
+
+        // Dropped while waiting
+        let guard = mutex.lock().await;
+        let mut first = Box::pin(mutex.lock());
+        let mut second = Box::pin(mutex.lock());
+        assert!(poll!(&mut first).is_pending());
+        assert!(poll!(&mut second).is_pending());
+        drop(first);
+        drop(guard);
+        drop(second.await);
+
+        // Dropped after being handed the lock, without ever seeing it
+        let guard = mutex.lock().await;
+        let mut first = Box::pin(mutex.lock());
+        let mut second = Box::pin(mutex.lock());
+        assert!(poll!(&mut first).is_pending());
+        assert!(poll!(&mut second).is_pending());
+        drop(guard);
+        drop(first);
+        second.await;
+    });
+}
+
+#[test]
+fn test_rwlock_shares_reads() {
+    on_both_executors(|_| async {
+        let lock = RwLock::new(5);
+        let first = lock.read().await;
+        let second = lock.read().await;
+        assert_eq!(*first + *second, 10);
+        assert!(lock.try_write().is_none());
+        drop((first, second));
+
+        *lock.write().await += 1;
+        assert_eq!(*lock.read().await, 6);
+    });
+}
+
+#[test]
+fn test_waiting_writer_holds_up_later_readers() {
+    on_both_executors(|_| async {
+        let lock = RwLock::new(0);
+        let reader = lock.read().await;
+        let mut writer = Box::pin(lock.write());
+        let mut later_reader = Box::pin(lock.read());
+        assert!(poll!(&mut writer).is_pending());
+        assert!(poll!(&mut later_reader).is_pending());
+        assert!(lock.try_read().is_none());
+
+        drop(reader);
+        let mut writer = writer.await;
+        *writer = 1;
+        assert!(poll!(&mut later_reader).is_pending());
+        drop(writer);
+        assert_eq!(*later_reader.await, 1);
+    });
+}
+
+#[test]
+fn test_semaphore_limits_concurrency() {
+    on_both_executors(|spawn| async move {
+        let semaphore = Arc::new(Semaphore::new(3));
+        let running = Arc::new(AtomicUsize::new(0));
+        let most_running = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..30)
+            .map(|_| {
+                let semaphore = semaphore.clone();
+                let running = running.clone();
+                let most_running = most_running.clone();
+                spawn(
+                    async move {
+                        let _permit = semaphore.acquire().await.unwrap();
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        most_running.fetch_max(now, Ordering::SeqCst);
+                        yield_now().await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    .boxed(),
+                )
+            })
+            .collect();
+        futures::future::join_all(tasks).await;
+
+        assert!(most_running.load(Ordering::SeqCst) <= 3);
+        assert_eq!(semaphore.available_permits(), 3);
+    });
+}
+
+#[test]
+fn test_large_requests_are_not_starved_by_small_ones() {
+    on_both_executors(|_| async {
+        let semaphore = Semaphore::new(2);
+        let held = semaphore.acquire().await.unwrap();
+        let mut large = Box::pin(semaphore.acquire_many(2));
+        assert!(poll!(&mut large).is_pending());
+
+        // One permit is free, but the large request is first in line
+        assert_eq!(semaphore.try_acquire().err(), Some(TryAcquireError::NoPermits));
+        let mut small = Box::pin(semaphore.acquire());
+        assert!(poll!(&mut small).is_pending());
+
+        drop(held);
+        let large = large.await.unwrap();
+        assert!(poll!(&mut small).is_pending());
+        drop(large);
+        small.await.unwrap();
+    });
+}
+
+#[test]
+fn test_closing_a_semaphore_fails_its_waiters() {
+    on_both_executors(|_| async {
+        let semaphore = Semaphore::new(1);
+        let permit = semaphore.acquire().await.unwrap();
+        let mut waiting = Box::pin(semaphore.acquire());
+        assert!(poll!(&mut waiting).is_pending());
+
+        semaphore.close();
+        assert!(waiting.await.is_err());
+        assert!(semaphore.acquire().await.is_err());
+        assert_eq!(semaphore.try_acquire().err(), Some(TryAcquireError::Closed));
+        drop(permit);
+    });
+}
+
+#[test]
+fn test_notify_one_is_saved_for_the_next_waiter() {
+    on_both_executors(|_| async {
+        let notify = Notify::new();
+        notify.notify_one();
+        notify.notify_one();
+        notify.notified().await;
+
+        // Only one was saved
+        let mut notified = Box::pin(notify.notified());
+        assert!(poll!(&mut notified).is_pending());
+    });
+}
+
+#[test]
+fn test_notify_one_wakes_waiters_in_order() {
+    on_both_executors(|_| async {
+        let notify = Notify::new();
+        let mut first = Box::pin(notify.notified());
+        let mut second = Box::pin(notify.notified());
+        let mut third = Box::pin(notify.notified());
+        assert!(poll!(&mut first).is_pending());
+        assert!(poll!(&mut second).is_pending());
+        assert!(poll!(&mut third).is_pending());
+
+        notify.notify_one();
+        assert!(poll!(&mut third).is_pending());
+        assert!(poll!(&mut second).is_pending());
+        first.await;
+
+        // Dropped after being notified, so it's passed on
+        notify.notify_one();
+        drop(second);
+        third.await;
+    });
+}
+
+#[test]
+fn test_notify_waiters_wakes_everyone_waiting() {
+    on_both_executors(|spawn| async move {
+        let notify = Arc::new(Notify::new());
+        let started = Arc::new(Semaphore::new(0));
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let notify = notify.clone();
+                let started = started.clone();
+                spawn(
+                    async move {
+                        let mut notified = Box::pin(notify.notified());
+                        assert!(poll!(&mut notified).is_pending());
+                        started.add_permits(1);
+                        notified.await;
+                    }
+                    .boxed(),
+                )
+            })
+            .collect();
+        started.acquire_many(10).await.unwrap().forget();
+
+        notify.notify_waiters();
+        futures::future::join_all(tasks).await;
+
+        // Nothing saved for later
+        let mut notified = Box::pin(notify.notified());
+        assert!(poll!(&mut notified).is_pending());
+    });
+}
+
+#[test]
+fn test_oneshot() {
+    on_both_executors(|spawn| async move {
+        let (sender, receiver) = oneshot::channel();
+        let sending = spawn(
+            async move {
+                yield_now().await;
+                sender.send("hello").unwrap();
+            }
+            .boxed(),
+        );
+        assert_eq!(receiver.await, Ok("hello"));
+        sending.await;
+
+        let (sender, receiver) = oneshot::channel::<()>();
+        drop(sender);
+        assert_eq!(receiver.await, Err(oneshot::RecvError));
+
+        let (sender, receiver) = oneshot::channel();
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(5), Err(5));
+    });
+}
+
+#[test]
+fn test_mpsc_delivers_everything_from_many_senders() {
+    on_both_executors(|spawn| async move {
+        let (sender, mut receiver) = mpsc::channel(4);
+        let senders: Vec<_> = (0..10u64)
+            .map(|n| {
+                let sender = sender.clone();
+                spawn(
+                    async move {
+                        for i in 0..100 {
+                            sender.send(n * 1_000 + i).await.unwrap();
+                        }
+                    }
+                    .boxed(),
+                )
+            })
+            .collect();
+        drop(sender);
+
+        let mut received = Vec::new();
+        while let Some(value) = receiver.recv().await {
+            received.push(value);
+        }
+        futures::future::join_all(senders).await;
+
+        assert_eq!(received.len(), 1_000);
+        // Each sender's values arrive in the order it sent them
+        for n in 0..10 {
+            let from_n: Vec<_> = received.iter().filter(|&&value| value / 1_000 == n).collect();
+            assert!(from_n.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    });
+}
+
+#[test]
+fn test_mpsc_waiting_senders_go_in_order() {
+    on_both_executors(|_| async {
+        let (sender, mut receiver) = mpsc::channel(1);
+        sender.send(0).await.unwrap();
+        assert_eq!(sender.try_send(9), Err(mpsc::TrySendError::Full(9)));
+
+        let mut first = Box::pin(sender.send(1));
+        let mut second = Box::pin(sender.send(2));
+        let mut third = Box::pin(sender.send(3));
+        assert!(poll!(&mut first).is_pending());
+        assert!(poll!(&mut second).is_pending());
+        assert!(poll!(&mut third).is_pending());
+        // Cancelled, so its value is never sent and it gives up its place
+        drop(second);
+
+        assert_eq!(receiver.recv().await, Some(0));
+        assert!(poll!(&mut third).is_pending());
+        first.await.unwrap();
+        assert_eq!(receiver.recv().await, Some(1));
+        third.await.unwrap();
+        assert_eq!(receiver.recv().await, Some(3));
+    });
+}
+
+#[test]
+fn test_mpsc_closing() {
+    on_both_executors(|_| async {
+        let (sender, mut receiver) = mpsc::channel(2);
+        sender.send(1).await.unwrap();
+        receiver.close();
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(2).await, Err(mpsc::SendError(2)));
+        // What was sent before closing can still be received
+        assert_eq!(receiver.recv().await, Some(1));
+        assert_eq!(receiver.recv().await, None);
+
+        let (sender, receiver) = mpsc::channel(1);
+        sender.send(1).await.unwrap();
+        let mut waiting = Box::pin(sender.send(2));
+        assert!(poll!(&mut waiting).is_pending());
+        drop(receiver);
+        assert_eq!(waiting.await, Err(mpsc::SendError(2)));
+    });
+}
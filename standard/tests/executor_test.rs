@@ -0,0 +1,349 @@
+mod common;
+
+use std::{
+    collections::HashSet,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    task::{Context, Poll},
+    thread,
+    time::Duration,
+};
+use futures::channel::oneshot;
+use standard::executor::{time, Executor, JoinError};
+use self::common::run_with_spawner;
+
+const THREAD_COUNTS: &[usize] = &[1, 2, 3, 4, 8];
+
+/// Returns `Pending` the first `count` times it's polled, waking itself
+/// each time so it goes straight back on the queue
+struct Yield {
+    count: usize,
+}
+
+impl Future for Yield {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.count == 0 {
+            return Poll::Ready(());
+        }
+        self.count -= 1;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Completes once another thread wakes it
+struct WokenFromElsewhere {
+    done: Arc<Mutex<bool>>,
+}
+
+impl Future for WokenFromElsewhere {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if *self.done.lock().unwrap() {
+            return Poll::Ready(());
+        }
+        let done = self.done.clone();
+        let waker = cx.waker().clone();
+        thread::spawn(move || {
+            *done.lock().unwrap() = true;
+            waker.wake();
+        });
+        Poll::Pending
+    }
+}
+
+#[test]
+fn test_thousands_of_tasks_complete_on_every_thread_count() {
+    const TASKS: usize = 5_000;
+
+    for &threads in THREAD_COUNTS {
+        let executor = Executor::new(threads);
+        let spawner = executor.spawner();
+        let completed = Arc::new(AtomicUsize::new(0));
+
+        for n in 0..TASKS {
+            let completed = completed.clone();
+            spawner.spawn(async move {
+                Yield { count: n % 4 }.await;
+                if n % 100 == 0 {
+                    WokenFromElsewhere { done: Arc::default() }.await;
+                }
+                completed.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        drop(spawner);
+        executor.run();
+
+        assert_eq!(completed.load(Ordering::SeqCst), TASKS, "with {} threads", threads);
+    }
+}
+
+#[test]
+fn test_tasks_can_spawn_tasks() {
+    for &threads in THREAD_COUNTS {
+        let executor = Executor::new(threads);
+        let spawner = executor.spawner();
+        let completed = Arc::new(AtomicUsize::new(0));
+
+        let inner_spawner = spawner.clone();
+        let inner_completed = completed.clone();
+        spawner.spawn(async move {
+            for _ in 0..1_000 {
+                let completed = inner_completed.clone();
+                inner_spawner.spawn(async move {
+                    Yield { count: 1 }.await;
+                    completed.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+        drop(spawner);
+        executor.run();
+
+        assert_eq!(completed.load(Ordering::SeqCst), 1_000, "with {} threads", threads);
+    }
+}
+
+#[test]
+fn test_idle_workers_steal_from_busy_ones() {
+    let executor = Executor::new(4);
+    let spawner = executor.spawner();
+    let threads_used = Arc::new(Mutex::new(HashSet::new()));
+
+    // Spawned from a worker, so all the children start on its local queue
+    let inner_spawner = spawner.clone();
+    let inner_threads_used = threads_used.clone();
+    spawner.spawn(async move {
+        for _ in 0..64 {
+            let threads_used = inner_threads_used.clone();
+            inner_spawner.spawn(async move {
+                thread::sleep(Duration::from_millis(2));
+                let name = thread::current().name().unwrap().to_string();
+                threads_used.lock().unwrap().insert(name);
+            });
+        }
+    });
+    drop(spawner);
+    executor.run();
+
+    assert!(threads_used.lock().unwrap().len() > 1);
+}
+
+#[test]
+fn test_run_returns_when_nothing_was_spawned() {
+    Executor::new(2).run();
+
+    let executor = Executor::new(2);
+    drop(executor.spawner());
+    executor.run();
+}
+
+#[test]
+fn test_spawners_made_after_the_others_were_dropped_still_work() {
+    let executor = Executor::new(2);
+    drop(executor.spawner());
+    let ran = Arc::new(AtomicBool::new(false));
+    {
+        let ran = ran.clone();
+        // Waits, so it needs the workers to still be running afterwards
+        executor.spawner().spawn(async move {
+            time::sleep(Duration::from_millis(10)).await;
+            ran.store(true, Ordering::SeqCst);
+        });
+    }
+
+    executor.run();
+
+    assert!(ran.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_tasks_nothing_can_wake_are_dropped() {
+    let executor = Executor::new(2);
+    let spawner = executor.spawner();
+    spawner.spawn(futures::future::pending::<()>());
+    drop(spawner);
+
+    // Would hang if the pending task kept the executor running
+    executor.run();
+}
+
+#[test]
+fn test_join_handle_returns_the_output() {
+    let sum = run_with_spawner(4, |spawner| async move {
+        let handles: Vec<_> = (0..100u64)
+            .map(|n| {
+                spawner.spawn(async move {
+                    Yield { count: 2 }.await;
+                    n * 2
+                })
+            })
+            .collect();
+
+        let mut sum = 0;
+        for handle in handles {
+            sum += handle.await.unwrap();
+        }
+        sum
+    });
+
+    assert_eq!(sum, 9_900);
+}
+
+#[test]
+fn test_join_handle_reports_a_panic() {
+    let result: Result<(), JoinError> = run_with_spawner(2, |spawner| async move {
+        spawner
+            .spawn(async {
+                Yield { count: 1 }.await;
+                panic!("boom");
+            })
+            .await
+    });
+
+    let err = result.unwrap_err();
+    assert!(err.is_panic());
+    assert!(!err.is_cancelled());
+    assert_eq!(err.to_string(), "task panicked: boom");
+    assert_eq!(*err.into_panic().downcast::<&str>().unwrap(), "boom");
+}
+
+#[test]
+fn test_join_handle_reports_cancellation() {
+    let result = run_with_spawner(2, |spawner| async move {
+        // Nothing can wake it, so it's dropped without finishing
+        spawner.spawn(futures::future::pending::<u32>()).await
+    });
+
+    let err = result.unwrap_err();
+    assert!(err.is_cancelled());
+    assert_eq!(err.to_string(), "task was cancelled");
+}
+
+#[test]
+fn test_dropping_the_join_handle_detaches_the_task() {
+    let completed = Arc::new(AtomicUsize::new(0));
+    let executor = Executor::new(2);
+    let spawner = executor.spawner();
+
+    let task_completed = completed.clone();
+    let handle = spawner.spawn(async move {
+        Yield { count: 3 }.await;
+        WokenFromElsewhere { done: Arc::default() }.await;
+        task_completed.fetch_add(1, Ordering::SeqCst);
+    });
+    drop(handle);
+    drop(spawner);
+    executor.run();
+
+    assert_eq!(completed.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_waking_more_tasks_than_ever_fitted_in_the_queue_does_not_block() {
+    // The ready queue used to be a channel of 10,000 that blocked the
+    // waking thread when full, with one worker that meant it blocked itself
+    const TASKS: usize = 20_000;
+
+    let (done_sender, done) = mpsc::channel();
+    thread::spawn(move || {
+        let executor = Executor::new(1);
+        let spawner = executor.spawner();
+        let completed = Arc::new(AtomicUsize::new(0));
+
+        let mut senders = Vec::new();
+        for _ in 0..TASKS {
+            let (sender, receiver) = oneshot::channel::<()>();
+            senders.push(sender);
+            let completed = completed.clone();
+            spawner.spawn(async move {
+                receiver.await.unwrap();
+                completed.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        // Wakes every task at once, from the only worker
+        spawner.spawn(async move {
+            Yield { count: 1 }.await;
+            for sender in senders {
+                sender.send(()).unwrap();
+            }
+        });
+        drop(spawner);
+        executor.run();
+        done_sender.send(completed.load(Ordering::SeqCst)).unwrap();
+    });
+
+    let completed = done.recv_timeout(Duration::from_secs(30)).expect("the executor deadlocked");
+    assert_eq!(completed, TASKS);
+}
+
+#[test]
+fn test_try_spawn_refuses_tasks_over_the_limit() {
+    let executor = Executor::new(2).max_tasks(2);
+    let spawner = executor.spawner();
+    let (release, released) = oneshot::channel::<()>();
+
+    spawner.try_spawn(async { released.await.unwrap() }).unwrap();
+    spawner.try_spawn(Yield { count: 1 }).unwrap();
+    let err = spawner.try_spawn(async { 3 }).unwrap_err();
+    assert_eq!(err.to_string(), "too many tasks are running");
+
+    // Admitted once the others have finished
+    release.send(()).unwrap();
+    let future = err.into_inner();
+    let output = Arc::new(Mutex::new(None));
+    let task_output = output.clone();
+    let inner_spawner = spawner.clone();
+    spawner.spawn(async move {
+        let handle = inner_spawner.wait_and_spawn(future).await;
+        *task_output.lock().unwrap() = Some(handle.await.unwrap());
+    });
+    drop(spawner);
+    executor.run();
+
+    assert_eq!(*output.lock().unwrap(), Some(3));
+}
+
+#[test]
+fn test_wait_and_spawn_keeps_tasks_under_the_limit() {
+    const MAX_TASKS: usize = 4;
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let most_running = Arc::new(AtomicUsize::new(0));
+    let completed = Arc::new(AtomicUsize::new(0));
+    let executor = Executor::new(4).max_tasks(MAX_TASKS);
+    let spawner = executor.spawner();
+
+    let inner_spawner = spawner.clone();
+    let task_most_running = most_running.clone();
+    let task_completed = completed.clone();
+    spawner.spawn(async move {
+        for _ in 0..100 {
+            let running = running.clone();
+            let most_running = task_most_running.clone();
+            let completed = task_completed.clone();
+            inner_spawner
+                .wait_and_spawn(async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most_running.fetch_max(now, Ordering::SeqCst);
+                    Yield { count: 3 }.await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    completed.fetch_add(1, Ordering::SeqCst);
+                })
+                .await;
+        }
+    });
+    drop(spawner);
+    executor.run();
+
+    assert_eq!(completed.load(Ordering::SeqCst), 100);
+    // The spawning task counts too. Checked out here, as a panic in a task
+    // only reaches its handle.
+    assert!(most_running.load(Ordering::SeqCst) < MAX_TASKS);
+}
@@ -0,0 +1,153 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+use standard::executor::{
+    time::{self, Elapsed},
+    Executor,
+};
+
+/// Runs `future` as the only task on a fresh executor and returns what it
+/// resolves to
+fn run<F, T>(threads: usize, future: F) -> T
+where
+    F: std::future::Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let executor = Executor::new(threads);
+    let output = Arc::new(Mutex::new(None));
+    let task_output = output.clone();
+    executor.spawner().spawn(async move {
+        *task_output.lock().unwrap() = Some(future.await);
+    });
+    executor.run();
+
+    let output = output.lock().unwrap().take();
+    output.expect("the task didn't finish")
+}
+
+#[cfg(target_os = "linux")]
+fn thread_count() -> usize {
+    std::fs::read_dir("/proc/self/task").unwrap().count()
+}
+
+#[test]
+fn test_sleep_waits_for_the_duration() {
+    let elapsed = run(2, async {
+        let start = Instant::now();
+        time::sleep(Duration::from_millis(50)).await;
+        start.elapsed()
+    });
+
+    assert!(elapsed >= Duration::from_millis(50));
+    assert!(elapsed < Duration::from_secs(2));
+}
+
+#[test]
+fn test_sleep_until_a_past_deadline_completes_straight_away() {
+    run(1, async {
+        let start = Instant::now();
+        time::sleep_until(start - Duration::from_secs(1)).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+    });
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_many_timers_need_no_threads_of_their_own() {
+    const TIMERS: usize = 10_000;
+
+    let executor = Executor::new(2);
+    let spawner = executor.spawner();
+    let completed = Arc::new(AtomicUsize::new(0));
+    let most_threads = Arc::new(AtomicUsize::new(0));
+
+    for n in 0..TIMERS {
+        let completed = completed.clone();
+        let most_threads = most_threads.clone();
+        spawner.spawn(async move {
+            time::sleep(Duration::from_millis(20 + (n % 50) as u64)).await;
+            most_threads.fetch_max(thread_count(), Ordering::SeqCst);
+            completed.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    drop(spawner);
+    let threads_before = thread_count();
+    executor.run();
+
+    assert_eq!(completed.load(Ordering::SeqCst), TIMERS);
+    // Two workers, plus whatever other tests are running alongside
+    assert!(most_threads.load(Ordering::SeqCst) < threads_before + 100);
+}
+
+#[test]
+fn test_timeout_returns_the_output_in_time() {
+    let result = run(2, async {
+        time::timeout(time::sleep(Duration::from_millis(10)), Duration::from_secs(5)).await
+    });
+
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn test_timeout_gives_up_on_a_slow_future() {
+    let (result, elapsed) = run(2, async {
+        let start = Instant::now();
+        let result = time::timeout(futures::future::pending::<()>(), Duration::from_millis(30)).await;
+        (result, start.elapsed())
+    });
+
+    assert_eq!(result, Err(Elapsed));
+    assert!(elapsed >= Duration::from_millis(30));
+    assert_eq!(Elapsed.to_string(), "deadline has elapsed");
+}
+
+#[test]
+fn test_dropped_timers_are_removed() {
+    let (during, after) = run(2, async {
+        let mut sleeps: Vec<_> = (0..100).map(|_| time::sleep(Duration::from_secs(60))).collect();
+        // Polled once each, so that they register with the driver
+        for sleep in &mut sleeps {
+            assert_eq!(futures::poll!(sleep), Poll::Pending);
+        }
+        let during = time::timers_pending();
+        drop(sleeps);
+        (during, time::timers_pending())
+    });
+
+    assert_eq!((during, after), (100, 0));
+}
+
+#[test]
+fn test_interval_ticks_at_its_period() {
+    let ticks = run(2, async {
+        let mut interval = time::interval(Duration::from_millis(20));
+        let mut ticks = Vec::new();
+        for _ in 0..4 {
+            ticks.push(interval.tick().await);
+        }
+        ticks
+    });
+
+    for pair in ticks.windows(2) {
+        assert!(pair[1] - pair[0] >= Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_reset_moves_the_deadline() {
+    let elapsed = run(1, async {
+        let start = Instant::now();
+        let mut sleep = time::sleep(Duration::from_secs(60));
+        assert_eq!(futures::poll!(&mut sleep), Poll::Pending);
+        sleep.reset(start + Duration::from_millis(20));
+        sleep.await;
+        start.elapsed()
+    });
+
+    assert!(elapsed < Duration::from_secs(2));
+}
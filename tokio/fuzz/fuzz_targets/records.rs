@@ -0,0 +1,15 @@
+#![no_main]
+use hello_world::tinydb::records::{read_records, write_records, Format};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for format in [Format::JsonLines, Format::Csv] {
+        // Every record that reads back cleanly must survive a round trip
+        let records: Vec<_> = read_records(format, data).filter_map(Result::ok).collect();
+
+        let mut out = Vec::new();
+        write_records(format, &mut out, records.clone()).unwrap();
+        let again: Vec<_> = read_records(format, &out[..]).collect::<Result<_, _>>().unwrap();
+        assert_eq!(again, records);
+    }
+});
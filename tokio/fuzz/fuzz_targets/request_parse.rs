@@ -0,0 +1,24 @@
+#![no_main]
+use std::collections::HashMap;
+use futures::executor::block_on;
+use hello_world::tinydb::{handle_request, Database, Request, Session};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let input = match std::str::from_utf8(data) {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+
+    // Whatever parses must serialize back to a line that parses the same
+    if let Ok(request) = Request::parse(input) {
+        assert_eq!(Request::parse(&request.serialize()), Ok(request));
+    }
+
+    // Treat the input as a session's worth of lines, none may panic
+    let db = Database::new(HashMap::new());
+    let mut session = Session::new(None);
+    for line in input.lines() {
+        block_on(handle_request(line, &db, &mut session)).serialize();
+    }
+});
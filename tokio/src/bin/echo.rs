@@ -1,31 +1,156 @@
-use tokio::net::TcpListener;
-use futures::stream::StreamExt;
+use std::{env, error::Error, sync::Arc, time::Duration};
+use futures::future::{BoxFuture, FutureExt};
+use hello_world::{
+    chat::Chat,
+    listen::{parse_mode, Connection, ListenAddr},
+    proxy::{self, LogMode},
+    server::{parse_max_connections, Server, DEFAULT_MAX_CONNECTIONS},
+    tls::TlsOptions,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// How many messages a chat room holds for a client that is slow to read
+const CHAT_BACKLOG: usize = 256;
+
+enum Mode {
+    Echo,
+    /// Broadcast lines to the other clients instead of echoing them
+    Chat(Arc<Chat>),
+    /// Forward connections to another server
+    Proxy(Arc<proxy::Config>),
+}
 
 #[tokio::main]
-async fn main() {
-    let addr = "127.0.0.1:6142";
-    let mut listener = TcpListener::bind(addr).await.unwrap();
-
-    let server = async move {
-        let mut incoming = listener.incoming();
-        while let Some(socket_res) = incoming.next().await {
-            match socket_res {
-                Ok(mut socket) => {
-                    println!("Accepted connection from {:?}", socket.peer_addr());
-                    tokio::spawn(async move {
-                        let (mut reader, mut writer) = socket.split();
-
-                        match tokio::io::copy(&mut reader, &mut writer).await {
-                            Ok(amt) => println!("wrote {} bytes", amt),
-                            Err(err) => eprintln!("IO error {:?}", err),
-                        }
-                    });
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut addrs = Vec::new();
+    let mut socket_mode = None;
+    let mut chat = false;
+    let mut upstream = None;
+    let mut log = None;
+    let mut latency = None;
+    let mut bandwidth = None;
+    let mut max_connections = DEFAULT_MAX_CONNECTIONS;
+    let mut tls_cert = None;
+    let mut tls_key = None;
+    let mut tls_client_ca = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                let addr = args.next().ok_or("--listen needs an address")?;
+                addrs.push(ListenAddr::parse(&addr));
+            }
+            "--socket-mode" => {
+                let mode = args.next().ok_or("--socket-mode needs a mode")?;
+                socket_mode = Some(parse_mode(&mode)?);
+            }
+            "--max-connections" => {
+                let max = args.next().ok_or("--max-connections needs a number")?;
+                max_connections = parse_max_connections(&max)?;
+            }
+            "--tls-cert" => tls_cert = Some(args.next().ok_or("--tls-cert needs a path")?.into()),
+            "--tls-key" => tls_key = Some(args.next().ok_or("--tls-key needs a path")?.into()),
+            "--tls-client-ca" => {
+                tls_client_ca = Some(args.next().ok_or("--tls-client-ca needs a path")?.into())
+            }
+            "--chat" => chat = true,
+            "--proxy" => {
+                let addr = args.next().ok_or("--proxy needs an upstream address")?;
+                upstream = Some(ListenAddr::parse(&addr));
+            }
+            "--log" => {
+                let mode = args.next().ok_or("--log needs hex or lines")?;
+                log = Some(LogMode::parse(&mode)?);
+            }
+            "--latency" => {
+                let ms = args.next().ok_or("--latency needs milliseconds")?;
+                latency = Some(Duration::from_millis(ms.parse()?));
+            }
+            "--bandwidth" => {
+                let rate = args.next().ok_or("--bandwidth needs bytes per second")?;
+                let rate: u64 = rate.parse()?;
+                if rate == 0 {
+                    return Err("--bandwidth must be more than 0".into());
+                }
+                bandwidth = Some(rate);
+            }
+            arg => return Err(format!("unexpected argument: {}", arg).into()),
+        }
+    }
+    if addrs.is_empty() {
+        addrs.push(ListenAddr::parse("127.0.0.1:6142"));
+    }
+
+    let mode = match upstream {
+        Some(_) if chat => return Err("--chat and --proxy can't be combined".into()),
+        Some(upstream) => Mode::Proxy(Arc::new(proxy::Config {
+            upstream,
+            log,
+            latency: latency.unwrap_or_default(),
+            bandwidth,
+        })),
+        None if log.is_some() || latency.is_some() || bandwidth.is_some() => {
+            return Err("--log, --latency and --bandwidth need --proxy".into())
+        }
+        None if chat => Mode::Chat(Arc::new(Chat::new(CHAT_BACKLOG))),
+        None => Mode::Echo,
+    };
+
+    let tls = match TlsOptions::from_paths(tls_cert, tls_key, tls_client_ca)? {
+        Some(options) => Some(options.acceptor()?),
+        None => None,
+    };
+
+    let mut server = Server::bind(&addrs, socket_mode).await?.max_connections(max_connections);
+    if let Some(acceptor) = tls {
+        server = server.tls(acceptor);
+    }
+    for addr in &addrs {
+        println!("Listening on {}", addr);
+    }
+
+    let handler = move |socket: Box<dyn Connection>, id: u64| -> BoxFuture<'static, ()> {
+        println!("Accepted connection {}", id);
+        match mode {
+            Mode::Echo => echo(socket).boxed(),
+            Mode::Chat(ref chat) => {
+                let chat = chat.clone();
+                async move { chat.handle_connection(socket).await }.boxed()
+            }
+            Mode::Proxy(ref config) => {
+                let config = config.clone();
+                async move {
+                    let (counters, result) = proxy::proxy(socket, &config, id).await;
+                    if let Err(err) = result {
+                        eprintln!("Proxy error on connection {}: {:?}", id, err);
+                    }
+                    println!(
+                        "Closed connection {}: {} bytes to upstream, {} bytes to client",
+                        id, counters.to_upstream, counters.to_client
+                    );
                 }
-                Err(err) => eprintln!("Accept error = {:?}", err)
+                .boxed()
             }
         }
     };
 
-    println!("Listening on {}", addr);
-    server.await;
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+        println!("Shutting down");
+    };
+    let open = server.run(handler, shutdown).await;
+    if open > 0 {
+        println!("Closing {} connections that are still open", open);
+    }
+    Ok(())
+}
+
+async fn echo<S: AsyncRead + AsyncWrite>(socket: S) {
+    let (mut reader, mut writer) = tokio::io::split(socket);
+
+    match tokio::io::copy(&mut reader, &mut writer).await {
+        Ok(amt) => println!("wrote {} bytes", amt),
+        Err(err) => eprintln!("IO error {:?}", err),
+    }
 }
@@ -12,17 +12,22 @@ impl Future for HelloWorld {
     }
 }
 
+/// Prints the output of the future it wraps. Any future can be wrapped,
+/// including one from an `async` block that borrows from itself.
 struct Display<T>(T);
 
 impl<T> Future for Display<T>
 where
-    T: Future + Unpin,
+    T: Future,
     T::Output: std::fmt::Display,
 {
     type Output = ();
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let inner = Pin::new(&mut self.0);
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: the inner future is pinned whenever `self` is. It's never
+        // moved out, `Display` has no `Drop` impl that could move it, and
+        // it isn't `Unpin` unless `T` is.
+        let inner = unsafe { self.map_unchecked_mut(|display| &mut display.0) };
         let value = match inner.poll(cx) {
             Poll::Ready(value) => value,
             Poll::Pending => return Poll::Pending,
@@ -38,3 +43,66 @@ async fn main() {
     let future = Display(HelloWorld);
     future.await
 }
+
+// These also run under Miri, to check the projection:
+// `cargo +nightly miri test --bin hello_future`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker_ref;
+
+    /// Returns `Pending` once before it completes
+    #[derive(Default)]
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    fn poll_once<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+        future.poll(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    async fn greeting(name: &str) -> String {
+        YieldOnce::default().await;
+        format!("Hello {}", name)
+    }
+
+    #[test]
+    fn test_display_an_unpin_future() {
+        let mut future = Display(HelloWorld);
+        assert_eq!(poll_once(Pin::new(&mut future)), Poll::Ready(()));
+    }
+
+    #[test]
+    fn test_display_a_self_referential_future() {
+        let future = Display(async {
+            let words = ["borrowed", "across", "an", "await"];
+            let first = &words[0];
+            YieldOnce::default().await;
+            first.len()
+        });
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(()));
+    }
+
+    #[test]
+    fn test_display_an_async_fn() {
+        let future = Display(greeting("world"));
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(()));
+    }
+}
@@ -0,0 +1,400 @@
+//! Load generator for tinydb that reports throughput and latency.
+//!
+//! ```text
+//! tinydb-bench [--server ADDR] [--user NAME] [--connections N] [--requests N]
+//!              [--keys N] [--set-ratio F] [--distribution uniform|zipf]
+//!              [--zipf-exponent F] [--value-size N] [--seed N] [--format text|json]
+//! ```
+//!
+//! Each connection sends one request at a time and waits for its response.
+//! Every key is SET before the clock starts so that GETs don't miss. Keys
+//! are named `bench:<n>`, so with authentication enabled `--user` needs
+//! `GET,SET` on `bench:*`, with the password in `TINYDB_PASSWORD`.
+
+use std::{
+    env,
+    error::Error,
+    time::{Duration, Instant},
+};
+use futures::{SinkExt, StreamExt};
+use hello_world::listen::{self, Connection, ListenAddr};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Zipf};
+use serde::Serialize;
+use tokio_util::codec::{Framed, LinesCodec};
+
+/// How many preload requests are sent before waiting for their responses,
+/// so that neither side blocks on a full socket buffer
+const PIPELINE_DEPTH: usize = 100;
+
+type Client = Framed<Box<dyn Connection>, LinesCodec>;
+type BoxError = Box<dyn Error + Send + Sync>;
+
+#[derive(Clone, Serialize)]
+struct Config {
+    server: String,
+    connections: usize,
+    requests: usize,
+    keys: usize,
+    set_ratio: f64,
+    distribution: KeyDistribution,
+    value_size: usize,
+    seed: u64,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase", tag = "kind")]
+enum KeyDistribution {
+    Uniform,
+    /// A few keys get most of the requests, the higher the exponent the more
+    /// skewed towards them
+    Zipf { exponent: f64 },
+}
+
+/// What one connection saw
+#[derive(Default)]
+struct Results {
+    gets: usize,
+    sets: usize,
+    errors: usize,
+    latencies: Vec<Duration>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    config: Config,
+    gets: usize,
+    sets: usize,
+    errors: usize,
+    elapsed_secs: f64,
+    requests_per_sec: f64,
+    latency_us: Latency,
+}
+
+#[derive(Serialize)]
+struct Latency {
+    p50: u128,
+    p99: u128,
+    p999: u128,
+    max: u128,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
+    let mut config = Config {
+        server: "127.0.0.1:8080".to_string(),
+        connections: 10,
+        requests: 100_000,
+        keys: 1000,
+        set_ratio: 0.1,
+        distribution: KeyDistribution::Uniform,
+        value_size: 16,
+        seed: 0,
+    };
+    let mut user = None;
+    let mut zipf_exponent = 1.0;
+    let mut json = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--server" => config.server = value()?,
+            "--user" => user = Some(value()?),
+            "--connections" => config.connections = value()?.parse()?,
+            "--requests" => config.requests = value()?.parse()?,
+            "--keys" => config.keys = value()?.parse()?,
+            "--set-ratio" => config.set_ratio = value()?.parse()?,
+            "--distribution" => {
+                config.distribution = match value()?.as_str() {
+                    "uniform" => KeyDistribution::Uniform,
+                    "zipf" => KeyDistribution::Zipf { exponent: 0.0 },
+                    other => return Err(format!("unknown distribution: {}", other).into()),
+                }
+            }
+            "--zipf-exponent" => zipf_exponent = value()?.parse()?,
+            "--value-size" => config.value_size = value()?.parse()?,
+            "--seed" => config.seed = value()?.parse()?,
+            "--format" => {
+                json = match value()?.as_str() {
+                    "text" => false,
+                    "json" => true,
+                    other => return Err(format!("unknown format: {}", other).into()),
+                }
+            }
+            arg => return Err(format!("unexpected argument: {}", arg).into()),
+        }
+    }
+    if let KeyDistribution::Zipf { ref mut exponent } = config.distribution {
+        *exponent = zipf_exponent;
+    }
+    if config.connections == 0 || config.keys == 0 {
+        return Err("--connections and --keys must be at least 1".into());
+    }
+    if !(0.0..=1.0).contains(&config.set_ratio) {
+        return Err("--set-ratio must be between 0 and 1".into());
+    }
+    // Checks the exponent before any connections are made
+    KeyPicker::new(config.distribution, config.keys)?;
+
+    let addr = ListenAddr::parse(&config.server);
+    let password = match user {
+        Some(_) => Some(
+            env::var("TINYDB_PASSWORD").map_err(|_| "--user needs the password in TINYDB_PASSWORD")?,
+        ),
+        None => None,
+    };
+    let auth = user.zip(password);
+
+    let mut clients = Vec::with_capacity(config.connections);
+    for _ in 0..config.connections {
+        clients.push(connect(&addr, auth.as_ref()).await?);
+    }
+    preload(&mut clients[0], &config).await?;
+
+    let start = Instant::now();
+    let mut workers = Vec::with_capacity(clients.len());
+    for (n, client) in clients.into_iter().enumerate() {
+        // Share out the remainder so exactly `requests` are sent
+        let requests = config.requests / config.connections
+            + usize::from(n < config.requests % config.connections);
+        let config = config.clone();
+        workers.push(tokio::spawn(async move {
+            run_connection(client, &config, n as u64, requests).await
+        }));
+    }
+
+    let mut total = Results::default();
+    for worker in workers {
+        let results = worker.await??;
+        total.gets += results.gets;
+        total.sets += results.sets;
+        total.errors += results.errors;
+        total.latencies.extend(results.latencies);
+    }
+    let elapsed = start.elapsed();
+
+    let report = Report::new(config, total, elapsed);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        report.print();
+    }
+    Ok(())
+}
+
+async fn connect(
+    addr: &ListenAddr,
+    auth: Option<&(String, String)>,
+) -> Result<Client, BoxError> {
+    let mut client = Framed::new(listen::connect(addr).await?, LinesCodec::new());
+    if let Some((user, password)) = auth {
+        let response = request(&mut client, format!("AUTH {} {}", user, password)).await?;
+        if let Some(msg) = response.strip_prefix("error: ") {
+            return Err(format!("could not authenticate: {}", msg).into());
+        }
+    }
+    Ok(client)
+}
+
+async fn request(client: &mut Client, line: String) -> Result<String, BoxError> {
+    client.send(line).await?;
+    match client.next().await {
+        Some(response) => Ok(response?),
+        None => Err("server closed the connection".into()),
+    }
+}
+
+/// Sets every key in the key space so the GETs during the run all hit
+async fn preload(client: &mut Client, config: &Config) -> Result<(), BoxError> {
+    let value = "x".repeat(config.value_size);
+    for start in (0..config.keys).step_by(PIPELINE_DEPTH) {
+        let batch = start..config.keys.min(start + PIPELINE_DEPTH);
+        for key in batch.clone() {
+            client.feed(format!("SET bench:{} {}", key, value)).await?;
+        }
+        client.flush().await?;
+        for _ in batch {
+            let response = client.next().await.ok_or("server closed the connection")??;
+            if let Some(msg) = response.strip_prefix("error: ") {
+                return Err(format!("could not preload keys: {}", msg).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_connection(
+    mut client: Client,
+    config: &Config,
+    connection: u64,
+    requests: usize,
+) -> Result<Results, BoxError> {
+    // Each connection gets its own stream of keys but the run as a whole is
+    // repeatable for a given seed
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(connection));
+    let keys = KeyPicker::new(config.distribution, config.keys)?;
+    let value = "x".repeat(config.value_size);
+    let mut results = Results {
+        latencies: Vec::with_capacity(requests),
+        ..Results::default()
+    };
+
+    for _ in 0..requests {
+        let key = keys.pick(&mut rng);
+        let line = if rng.gen_bool(config.set_ratio) {
+            results.sets += 1;
+            format!("SET bench:{} {}", key, value)
+        } else {
+            results.gets += 1;
+            format!("GET bench:{}", key)
+        };
+
+        let start = Instant::now();
+        let response = request(&mut client, line).await?;
+        results.latencies.push(start.elapsed());
+
+        if response.starts_with("error: ") {
+            results.errors += 1;
+        }
+    }
+
+    Ok(results)
+}
+
+enum KeyPicker {
+    Uniform(usize),
+    Zipf(Zipf<f64>),
+}
+
+impl KeyPicker {
+    fn new(distribution: KeyDistribution, keys: usize) -> Result<KeyPicker, String> {
+        match distribution {
+            KeyDistribution::Uniform => Ok(KeyPicker::Uniform(keys)),
+            KeyDistribution::Zipf { exponent } => Zipf::new(keys as u64, exponent)
+                .map(KeyPicker::Zipf)
+                .map_err(|e| format!("invalid zipf exponent {}: {}", exponent, e)),
+        }
+    }
+
+    /// A key number in `0..keys`, with zipf 0 is the most popular
+    fn pick<R: Rng>(&self, rng: &mut R) -> usize {
+        match *self {
+            KeyPicker::Uniform(keys) => rng.gen_range(0..keys),
+            // Zipf samples are ranks from 1 to keys inclusive
+            KeyPicker::Zipf(ref zipf) => zipf.sample(rng) as usize - 1,
+        }
+    }
+}
+
+impl Report {
+    fn new(config: Config, mut results: Results, elapsed: Duration) -> Report {
+        results.latencies.sort();
+        let latencies = &results.latencies;
+        let requests = results.gets + results.sets;
+        Report {
+            config,
+            gets: results.gets,
+            sets: results.sets,
+            errors: results.errors,
+            elapsed_secs: elapsed.as_secs_f64(),
+            requests_per_sec: requests as f64 / elapsed.as_secs_f64(),
+            latency_us: Latency {
+                p50: percentile(latencies, 500).as_micros(),
+                p99: percentile(latencies, 990).as_micros(),
+                p999: percentile(latencies, 999).as_micros(),
+                max: latencies.last().copied().unwrap_or_default().as_micros(),
+            },
+        }
+    }
+
+    fn print(&self) {
+        println!(
+            "{} connections, {} keys, {:.0}% sets",
+            self.config.connections,
+            self.config.keys,
+            self.config.set_ratio * 100.0
+        );
+        println!(
+            "requests:   {} ({} GET, {} SET, {} errors)",
+            self.gets + self.sets,
+            self.gets,
+            self.sets,
+            self.errors
+        );
+        println!("elapsed:    {:.3}s", self.elapsed_secs);
+        println!("throughput: {:.0} req/s", self.requests_per_sec);
+        println!(
+            "latency:    p50 {}us, p99 {}us, p999 {}us, max {}us",
+            self.latency_us.p50, self.latency_us.p99, self.latency_us.p999, self.latency_us.max
+        );
+    }
+}
+
+/// Nearest-rank percentile of sorted samples, zero when there are none. The
+/// percentile is given in thousandths, so p99.9 is 999, to keep it exact.
+fn percentile(sorted: &[Duration], per_mille: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    let rank = (per_mille * sorted.len()).div_ceil(1000);
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<_> = (1..=1000).map(ms).collect();
+
+        assert_eq!(percentile(&samples, 500), ms(500));
+        assert_eq!(percentile(&samples, 990), ms(990));
+        assert_eq!(percentile(&samples, 999), ms(999));
+        assert_eq!(percentile(&samples, 1000), ms(1000));
+        assert_eq!(percentile(&samples, 0), ms(1));
+    }
+
+    #[test]
+    fn test_percentile_of_few_samples() {
+        assert_eq!(percentile(&[], 500), ms(0));
+        assert_eq!(percentile(&[ms(7)], 999), ms(7));
+        assert_eq!(percentile(&[ms(1), ms(2)], 500), ms(1));
+        assert_eq!(percentile(&[ms(1), ms(2)], 990), ms(2));
+    }
+
+    #[test]
+    fn test_key_picker_stays_in_key_space() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for distribution in &[KeyDistribution::Uniform, KeyDistribution::Zipf { exponent: 1.2 }] {
+            let picker = KeyPicker::new(*distribution, 10).unwrap();
+            for _ in 0..10_000 {
+                assert!(picker.pick(&mut rng) < 10);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zipf_favours_low_keys() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let picker = KeyPicker::new(KeyDistribution::Zipf { exponent: 1.0 }, 100).unwrap();
+
+        let mut counts = [0; 100];
+        for _ in 0..10_000 {
+            counts[picker.pick(&mut rng)] += 1;
+        }
+
+        assert!(counts[0] > counts[1]);
+        assert!(counts[1] > counts[50]);
+    }
+
+    #[test]
+    fn test_invalid_zipf_exponent() {
+        assert!(KeyPicker::new(KeyDistribution::Zipf { exponent: -1.0 }, 10).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+//! Exports tinydb data to, and imports it from, JSON Lines or CSV files.
+//!
+//! ```text
+//! tinydb-bulk export [--server ADDR | --data PATH] [--prefix PREFIX] [--format jsonl|csv] [OUTPUT]
+//! tinydb-bulk import [--server ADDR | --data PATH] [--skip-existing] [--format jsonl|csv] [INPUT]
+//! ```
+//!
+//! `--server` talks to a running tinydb, `127.0.0.1:8080` by default, and
+//! `--user NAME` authenticates with the password in `TINYDB_PASSWORD`.
+//! `--data` works offline on a persistence log. Exporting only reads it, but
+//! importing rewrites it, so then it must not be open in a running server at
+//! the same time. Without a path the data is written to stdout or read from
+//! stdin.
+
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process,
+};
+use futures::{SinkExt, StreamExt};
+use hello_world::{
+    listen::{self, Connection, ListenAddr},
+    tinydb::{
+        records::{read_records, write_records, Format, Record},
+        Database,
+    },
+};
+use tokio_util::codec::{Framed, LinesCodec};
+
+/// How many requests are sent before waiting for their responses
+const PIPELINE_DEPTH: usize = 100;
+
+type Client = Framed<Box<dyn Connection>, LinesCodec>;
+
+enum Target {
+    Server { addr: ListenAddr, user: Option<String> },
+    Data(PathBuf),
+}
+
+#[derive(Default)]
+struct Summary {
+    imported: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let command = args.next().ok_or("expected export or import")?;
+
+    let mut server = None;
+    let mut user = None;
+    let mut data = None;
+    let mut prefix = String::new();
+    let mut format = Format::JsonLines;
+    let mut skip_existing = false;
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--server" => server = Some(args.next().ok_or("--server needs an address")?),
+            "--user" => user = Some(args.next().ok_or("--user needs a name")?),
+            "--data" => data = Some(args.next().ok_or("--data needs a path")?),
+            "--prefix" => prefix = args.next().ok_or("--prefix needs a prefix")?,
+            "--format" => format = Format::parse(&args.next().ok_or("--format needs a format")?)?,
+            "--skip-existing" => skip_existing = true,
+            _ if path.is_none() => path = Some(arg),
+            arg => return Err(format!("unexpected argument: {}", arg).into()),
+        }
+    }
+
+    let target = match (server, data) {
+        (Some(_), Some(_)) => return Err("--server and --data can't be used together".into()),
+        (None, Some(data)) => Target::Data(data.into()),
+        (server, None) => Target::Server {
+            addr: ListenAddr::parse(server.as_deref().unwrap_or("127.0.0.1:8080")),
+            user,
+        },
+    };
+
+    match command.as_str() {
+        "export" => {
+            let out: Box<dyn Write> = match path {
+                Some(path) => Box::new(File::create(path)?),
+                None => Box::new(io::stdout()),
+            };
+            let records = match target {
+                Target::Server { addr, user } => {
+                    let mut client = connect(&addr, user.as_deref()).await?;
+                    dump(&mut client, &prefix).await?
+                }
+                Target::Data(path) => export_from_data(&path, &prefix)?,
+            };
+            let count = write_records(format, out, records)?;
+            eprintln!("exported {} keys", count);
+        }
+        "import" => {
+            let input: Box<dyn Read> = match path {
+                Some(path) => Box::new(File::open(path)?),
+                None => Box::new(io::stdin()),
+            };
+            let records = read_records(format, input);
+            let summary = match target {
+                Target::Server { addr, user } => {
+                    let mut client = connect(&addr, user.as_deref()).await?;
+                    import_to_server(&mut client, records, skip_existing).await?
+                }
+                Target::Data(path) => import_to_data(&Database::open(&path)?, records, skip_existing),
+            };
+            eprintln!(
+                "imported {} keys, skipped {}, failed {}",
+                summary.imported, summary.skipped, summary.failed
+            );
+            if summary.failed > 0 {
+                process::exit(1);
+            }
+        }
+        command => return Err(format!("unknown command: {}", command).into()),
+    }
+
+    Ok(())
+}
+
+async fn connect(addr: &ListenAddr, user: Option<&str>) -> Result<Client, Box<dyn Error>> {
+    let mut client = Framed::new(listen::connect(addr).await?, LinesCodec::new());
+
+    if let Some(user) = user {
+        let password = env::var("TINYDB_PASSWORD")
+            .map_err(|_| "--user needs the password in TINYDB_PASSWORD")?;
+        client.send(format!("AUTH {} {}", user, password)).await?;
+        let response = next_line(&mut client).await?;
+        if let Some(msg) = response.strip_prefix("error: ") {
+            return Err(format!("could not authenticate: {}", msg).into());
+        }
+    }
+
+    Ok(client)
+}
+
+async fn next_line(client: &mut Client) -> Result<String, Box<dyn Error>> {
+    match client.next().await {
+        Some(line) => Ok(line?),
+        None => Err("server closed the connection".into()),
+    }
+}
+
+async fn dump(client: &mut Client, prefix: &str) -> Result<Vec<Record>, Box<dyn Error>> {
+    client.send(format!("DUMP {}", prefix)).await?;
+
+    let mut records = Vec::new();
+    loop {
+        let line = next_line(client).await?;
+        // Keys never contain spaces so the first " = " ends the key
+        if let Some((key, value)) = line.split_once(" = ") {
+            if !key.contains(' ') {
+                records.push(Record {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                });
+                continue;
+            }
+        }
+        if line.starts_with("end of dump") {
+            return Ok(records);
+        }
+        return Err(line.into());
+    }
+}
+
+async fn import_to_server<I>(
+    client: &mut Client,
+    records: I,
+    skip_existing: bool,
+) -> Result<Summary, Box<dyn Error>>
+where
+    I: Iterator<Item = Result<Record, String>>,
+{
+    let command = if skip_existing { "SETNX" } else { "SET" };
+    let mut summary = Summary::default();
+    let mut in_flight = Vec::with_capacity(PIPELINE_DEPTH);
+    let mut records = records.peekable();
+
+    while records.peek().is_some() {
+        for result in records.by_ref() {
+            match result {
+                Ok(record) => {
+                    client.feed(format!("{} {} {}", command, record.key, record.value)).await?;
+                    in_flight.push(record.key);
+                    if in_flight.len() == PIPELINE_DEPTH {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    summary.failed += 1;
+                }
+            }
+        }
+        client.flush().await?;
+
+        for key in in_flight.drain(..) {
+            let response = next_line(client).await?;
+            if response.starts_with("set ") {
+                summary.imported += 1;
+            } else if response.starts_with("exists ") {
+                summary.skipped += 1;
+            } else {
+                eprintln!("error: {}: {}", key, response);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Never creates or rewrites the log, unlike `Database::open`
+fn export_from_data(path: &Path, prefix: &str) -> io::Result<Vec<Record>> {
+    Ok(Database::load(path)?.dump(prefix))
+}
+
+fn import_to_data<I>(db: &Database, records: I, skip_existing: bool) -> Summary
+where
+    I: Iterator<Item = Result<Record, String>>,
+{
+    let mut summary = Summary::default();
+
+    for result in records {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                summary.failed += 1;
+                continue;
+            }
+        };
+
+        let key = record.key.clone();
+        let result = if skip_existing {
+            db.set_if_absent(record.key, record.value).map(|existing| existing.is_none())
+        } else {
+            db.set(record.key, record.value).map(|_| true)
+        };
+        match result {
+            Ok(true) => summary.imported += 1,
+            Ok(false) => summary.skipped += 1,
+            Err(e) => {
+                eprintln!("error: {}: {}", key, e);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn records() -> Vec<Result<Record, String>> {
+        vec![
+            Ok(Record { key: "foo".into(), value: "new".into() }),
+            Err("line 2: bad record".into()),
+            Ok(Record { key: "other".into(), value: "1".into() }),
+        ]
+    }
+
+    fn db() -> Database {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), "old".to_string());
+        Database::new(map)
+    }
+
+    #[test]
+    fn test_import_overwrites() {
+        let db = db();
+
+        let summary = import_to_data(&db, records().into_iter(), false);
+
+        assert_eq!((summary.imported, summary.skipped, summary.failed), (2, 0, 1));
+        assert_eq!(db.get("foo").unwrap(), "new");
+        assert_eq!(db.get("other").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_import_skips_existing() {
+        let db = db();
+
+        let summary = import_to_data(&db, records().into_iter(), true);
+
+        assert_eq!((summary.imported, summary.skipped, summary.failed), (1, 1, 1));
+        assert_eq!(db.get("foo").unwrap(), "old");
+        assert_eq!(db.get("other").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_export_from_a_missing_log_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("typo.jsonl");
+
+        let err = export_from_data(&path, "").err().unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(!path.exists());
+    }
+}
@@ -1,142 +1,139 @@
 use std::{
     collections::HashMap,
+    env,
     error::Error,
-    sync::{Arc, Mutex},
+    fs,
+    path::Path,
+    sync::Arc,
 };
 use futures::{SinkExt, StreamExt};
+use hello_world::{
+    listen::{parse_mode, Connection, ListenAddr},
+    server::{parse_max_connections, Server, DEFAULT_MAX_CONNECTIONS},
+    tinydb::{
+        acl::{hash_password, Acl},
+        handle_request, Database, Session,
+    },
+    tls::TlsOptions,
+};
 use tokio::{
     self,
-    net::TcpListener,
+    io::{AsyncRead, AsyncWrite},
 };
 use tokio_util::codec::{Framed, LinesCodec};
 
-struct Database {
-    map: Mutex<HashMap<String, String>>
-}
-
-enum Request {
-    Get { key: String },
-    Set { key: String, value: String },
-}
-
-enum Response {
-    Value {
-        key: String,
-        value: String,
-    },
-    Set {
-        key: String,
-        value: String,
-        previous: Option<String>,
-    },
-    Error {
-        msg: String,
-    }
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let addr = "127.0.0.1:8080";
-    let mut listener = TcpListener::bind(&addr).await?;
-
-    let mut initial_db = HashMap::new();
-    initial_db.insert("foo".to_string(), "bar".to_string());
-    let db = Arc::new(Database {
-        map: Mutex::new(initial_db),
-    });
-
-    loop {
-        match listener.accept().await {
-            Err(e) => println!("error accepting socket; error = {:?}", e),
-            Ok((socket, _)) => {
-                let db = db.clone();
-
-                tokio::spawn(async move {
-                    let mut lines = Framed::new(socket, LinesCodec::new());
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("hash-password") {
+        let password = args.nth(1).ok_or("hash-password needs a password")?;
+        println!("{}", hash_password(&password)?);
+        return Ok(());
+    }
 
-                    while let Some(result) = lines.next().await {
-                        match result {
-                            Ok(line) => {
-                                let response = handle_request(&line, &db);
-                                let response = response.serialize();
-                                if let Err(e) = lines.send(response).await {
-                                    println!("error on sending response; error = {:?}", e)
-                                }
-                            }
-                            Err(e) => {
-                                println!("error on sending response; error = {:?}", e);
-                            }
-                        }
-                    }
-                });
+    let mut addrs = Vec::new();
+    let mut socket_mode = None;
+    let mut data_path = None;
+    let mut config_path = None;
+    let mut max_connections = DEFAULT_MAX_CONNECTIONS;
+    let mut tls_cert = None;
+    let mut tls_key = None;
+    let mut tls_client_ca = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                let addr = args.next().ok_or("--listen needs an address")?;
+                addrs.push(ListenAddr::parse(&addr));
             }
+            "--socket-mode" => {
+                let mode = args.next().ok_or("--socket-mode needs a mode")?;
+                socket_mode = Some(parse_mode(&mode)?);
+            }
+            "--max-connections" => {
+                let max = args.next().ok_or("--max-connections needs a number")?;
+                max_connections = parse_max_connections(&max)?;
+            }
+            "--tls-cert" => tls_cert = Some(args.next().ok_or("--tls-cert needs a path")?.into()),
+            "--tls-key" => tls_key = Some(args.next().ok_or("--tls-key needs a path")?.into()),
+            "--tls-client-ca" => {
+                tls_client_ca = Some(args.next().ok_or("--tls-client-ca needs a path")?.into())
+            }
+            "--data" => data_path = Some(args.next().ok_or("--data needs a path")?),
+            _ if config_path.is_none() => config_path = Some(arg),
+            arg => return Err(format!("unexpected argument: {}", arg).into()),
         }
     }
-}
+    if addrs.is_empty() {
+        addrs.push(ListenAddr::parse("127.0.0.1:8080"));
+    }
 
-fn handle_request(line: &str, db: &Arc<Database>) -> Response {
-    let request = match Request::parse(&line) {
-        Ok(req) => req,
-        Err(e) => return Response::Error { msg: e },
+    let acl = match config_path {
+        Some(path) => {
+            let config = fs::read_to_string(path)?;
+            Some(Arc::new(Acl::parse(&config)?))
+        }
+        None => {
+            println!("warning: no config file given, running without authentication");
+            None
+        }
     };
 
-    let mut db = db.map.lock().unwrap();
-    match request {
-        Request::Get { key } => match db.get(&key) {
-            Some(value) => Response::Value {
-                key,
-                value: value.clone(),
-            },
-            None => Response::Error {
-                msg: format!("no key {}", key),
-            },
-        }
-        Request::Set { key, value } => {
-            let previous = db.insert(key.clone(), value.clone());
-            Response::Set {
-                key, value, previous,
-            }
+    let db = match data_path {
+        Some(path) => Database::open(Path::new(&path))?,
+        None => {
+            let mut initial_db = HashMap::new();
+            initial_db.insert("foo".to_string(), "bar".to_string());
+            Database::new(initial_db)
         }
+    };
+    let db = Arc::new(db);
+
+    let tls = match TlsOptions::from_paths(tls_cert, tls_key, tls_client_ca)? {
+        Some(options) => Some(options.acceptor()?),
+        None => None,
+    };
+
+    let mut server = Server::bind(&addrs, socket_mode).await?.max_connections(max_connections);
+    if let Some(acceptor) = tls {
+        server = server.tls(acceptor);
+    }
+    for addr in &addrs {
+        println!("Listening on {}", addr);
     }
+
+    let handler = move |socket: Box<dyn Connection>, _id: u64| {
+        let session = Session::new(acl.clone());
+        handle_connection(socket, db.clone(), session)
+    };
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+        println!("Shutting down");
+    };
+    let open = server.run(handler, shutdown).await;
+    if open > 0 {
+        println!("Closing {} connections that are still open", open);
+    }
+    Ok(())
 }
 
-impl Request {
-    fn parse(input: &str) -> Result<Request, String> {
-        let mut parts = input.splitn(3, ' ');
-        match parts.next() {
-            Some("GET") => {
-                let key = parts.next().ok_or("GET must be followed by a key")?;
-                if parts.next().is_some() {
-                    return Err("GET's key must not be followed by anything".into());
+async fn handle_connection<S>(socket: S, db: Arc<Database>, mut session: Session)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut lines = Framed::new(socket, LinesCodec::new());
+
+    while let Some(result) = lines.next().await {
+        match result {
+            Ok(line) => {
+                let response = handle_request(&line, &db, &mut session).await;
+                let response = response.serialize();
+                if let Err(e) = lines.send(response).await {
+                    println!("error on sending response; error = {:?}", e)
                 }
-                Ok(Request::Get {
-                    key: key.to_string(),
-                })
             }
-            Some("SET") => {
-                let key = parts.next().ok_or("SET must be followed by a key")?;
-                let value = parts.next().ok_or("SET needs a value")?;
-                Ok(Request::Set {
-                    key: key.to_string(),
-                    value: value.to_string(),
-                })
+            Err(e) => {
+                println!("error on sending response; error = {:?}", e);
             }
-            Some(cmd) => Err(format!("unknown command: {}", cmd)),
-            None => Err("empty input".into()),
-        }
-    }
-}
-
-impl Response {
-    fn serialize(&self) -> String {
-        match *self {
-            Response::Value { ref key, ref value } => format!("{} = {}", key, value),
-            Response::Set {
-                ref key,
-                ref value,
-                ref previous,
-            } => format!("set {} = {}, previous = {:?}", key, value, previous),
-            Response::Error { ref msg } => format!("error: {}", msg),
         }
     }
 }
@@ -0,0 +1,37 @@
+//! Sends every UDP datagram back to where it came from.
+//!
+//! ```text
+//! udp-echo [--listen ADDR] [--drop PCT] [--duplicate PCT] [--reorder PCT] [--seed N]
+//! ```
+//!
+//! The fault options mistreat that percentage of datagrams. A reordered
+//! datagram is held back until the next one has been sent, or for 50ms if
+//! nothing else arrives.
+
+use std::{env, error::Error};
+use hello_world::udp::{self, parse_percent, Faults};
+use tokio::net::UdpSocket;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut addr = "127.0.0.1:6143".to_string();
+    let mut faults = Faults::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--listen" => addr = value()?,
+            "--drop" => faults.drop = parse_percent(&value()?)?,
+            "--duplicate" => faults.duplicate = parse_percent(&value()?)?,
+            "--reorder" => faults.reorder = parse_percent(&value()?)?,
+            "--seed" => faults.seed = value()?.parse()?,
+            arg => return Err(format!("unexpected argument: {}", arg).into()),
+        }
+    }
+
+    let socket = UdpSocket::bind(&addr).await?;
+    println!("Listening on udp {}", socket.local_addr()?);
+    udp::serve(socket, faults).await?;
+    Ok(())
+}
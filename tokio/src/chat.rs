@@ -0,0 +1,269 @@
+//! A line based chat server, every line a client sends is broadcast to the
+//! other clients in the same room.
+//!
+//! A client's first line picks its nickname, after that lines starting with
+//! `/` are commands:
+//!
+//! * `/join <room>` leaves the current room for another, everyone starts in
+//!   `lobby`
+//! * `/nick <name>` changes nickname
+//! * `/who` lists who is in the current room
+//!
+//! Messages from the server start with `* `.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::broadcast::{self, RecvError},
+};
+use tokio_util::codec::{Framed, LinesCodec, LinesCodecError};
+
+const LOBBY: &str = "lobby";
+
+/// Longer lines are dropped rather than buffered without limit
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+pub struct Chat {
+    state: Mutex<State>,
+    next_id: AtomicU64,
+    /// Messages a room keeps for a slow client before it starts missing them
+    capacity: usize,
+}
+
+struct State {
+    nicks: HashSet<String>,
+    rooms: HashMap<String, Room>,
+}
+
+struct Room {
+    sender: broadcast::Sender<Message>,
+    members: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug)]
+struct Message {
+    /// The connection it came from, which doesn't get its own messages back
+    from: u64,
+    text: String,
+}
+
+/// One connected client, after it has picked a nickname
+struct Member {
+    id: u64,
+    nick: String,
+    room: String,
+}
+
+enum Input {
+    Say(String),
+    Join(String),
+    Nick(String),
+    Who,
+    Invalid(String),
+}
+
+impl Chat {
+    pub fn new(capacity: usize) -> Chat {
+        Chat {
+            state: Mutex::new(State {
+                nicks: HashSet::new(),
+                rooms: HashMap::new(),
+            }),
+            next_id: AtomicU64::new(0),
+            capacity,
+        }
+    }
+
+    pub async fn handle_connection<S>(&self, socket: S)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let lines = Framed::new(socket, LinesCodec::new_with_max_length(MAX_LINE_LENGTH));
+        let (mut sink, mut stream) = lines.split();
+
+        if sink.send("* welcome, pick a nickname".to_string()).await.is_err() {
+            return;
+        }
+        let nick = loop {
+            let nick = match stream.next().await {
+                Some(Ok(line)) => line,
+                Some(Err(LinesCodecError::MaxLineLengthExceeded)) => continue,
+                Some(Err(LinesCodecError::Io(_))) | None => return,
+            };
+            let reply = match validate_nick(&nick) {
+                Ok(()) if self.claim_nick(&nick) => break nick,
+                Ok(()) => format!("* nickname {} is taken", nick),
+                Err(e) => format!("* {}", e),
+            };
+            if sink.send(reply).await.is_err() {
+                return;
+            }
+        };
+
+        let mut member = Member {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            nick,
+            room: LOBBY.to_string(),
+        };
+        let mut receiver = self.enter(&member);
+        let mut reply = Some(format!("* you are {}, in {}", member.nick, member.room));
+
+        loop {
+            if let Some(line) = reply.take() {
+                if sink.send(line).await.is_err() {
+                    break;
+                }
+            }
+
+            tokio::select! {
+                line = stream.next() => {
+                    let line = match line {
+                        Some(Ok(line)) => line,
+                        Some(Err(LinesCodecError::MaxLineLengthExceeded)) => {
+                            reply = Some(format!("* lines are limited to {} bytes", MAX_LINE_LENGTH));
+                            continue;
+                        }
+                        // The connection is broken, so there's no one to tell
+                        Some(Err(LinesCodecError::Io(_))) | None => break,
+                    };
+                    reply = match Input::parse(line) {
+                        Input::Say(text) => {
+                            self.broadcast(&member, format!("{}: {}", member.nick, text));
+                            None
+                        }
+                        Input::Join(room) => {
+                            self.leave(&member);
+                            member.room = room;
+                            receiver = self.enter(&member);
+                            Some(format!("* you joined {}", member.room))
+                        }
+                        Input::Nick(nick) => Some(self.rename(&mut member, nick)),
+                        Input::Who => Some(self.who(&member)),
+                        Input::Invalid(msg) => Some(format!("* {}", msg)),
+                    };
+                }
+                message = receiver.recv() => {
+                    reply = match message {
+                        Ok(message) if message.from == member.id => None,
+                        Ok(message) => Some(message.text),
+                        // The room dropped messages this client was too slow
+                        // to take, rather than making everyone else wait
+                        Err(RecvError::Lagged(missed)) => Some(format!("* missed {} messages", missed)),
+                        // Can't happen, the room outlives its members
+                        Err(RecvError::Closed) => break,
+                    };
+                }
+            }
+        }
+
+        self.leave(&member);
+        self.state.lock().unwrap().nicks.remove(&member.nick);
+    }
+
+    fn claim_nick(&self, nick: &str) -> bool {
+        self.state.lock().unwrap().nicks.insert(nick.to_string())
+    }
+
+    /// Joins the member's room, creating it if needed, and tells the others
+    fn enter(&self, member: &Member) -> broadcast::Receiver<Message> {
+        let mut state = self.state.lock().unwrap();
+        let capacity = self.capacity;
+        let room = state.rooms.entry(member.room.clone()).or_insert_with(|| Room {
+            sender: broadcast::channel(capacity).0,
+            members: BTreeSet::new(),
+        });
+        room.members.insert(member.nick.clone());
+        let receiver = room.sender.subscribe();
+        drop(state);
+
+        self.broadcast(member, format!("* {} joined {}", member.nick, member.room));
+        receiver
+    }
+
+    /// Leaves the member's room, removing it once it's empty
+    fn leave(&self, member: &Member) {
+        self.broadcast(member, format!("* {} left {}", member.nick, member.room));
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(room) = state.rooms.get_mut(&member.room) {
+            room.members.remove(&member.nick);
+            if room.members.is_empty() {
+                state.rooms.remove(&member.room);
+            }
+        }
+    }
+
+    fn rename(&self, member: &mut Member, nick: String) -> String {
+        if let Err(e) = validate_nick(&nick) {
+            return format!("* {}", e);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if !state.nicks.insert(nick.clone()) {
+            return format!("* nickname {} is taken", nick);
+        }
+        state.nicks.remove(&member.nick);
+        if let Some(room) = state.rooms.get_mut(&member.room) {
+            room.members.remove(&member.nick);
+            room.members.insert(nick.clone());
+        }
+        drop(state);
+
+        let old = std::mem::replace(&mut member.nick, nick);
+        self.broadcast(member, format!("* {} is now {}", old, member.nick));
+        format!("* you are now {}", member.nick)
+    }
+
+    fn who(&self, member: &Member) -> String {
+        let state = self.state.lock().unwrap();
+        let members: Vec<&str> = state.rooms[&member.room].members.iter().map(String::as_str).collect();
+        format!("* in {}: {}", member.room, members.join(", "))
+    }
+
+    fn broadcast(&self, member: &Member, text: String) {
+        let state = self.state.lock().unwrap();
+        if let Some(room) = state.rooms.get(&member.room) {
+            // Only fails when nobody is subscribed, which is fine
+            let _ = room.sender.send(Message { from: member.id, text });
+        }
+    }
+}
+
+impl Input {
+    fn parse(line: String) -> Input {
+        if !line.starts_with('/') {
+            return Input::Say(line);
+        }
+
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("/join"), Some(room), None) => Input::Join(room.to_string()),
+            (Some("/join"), _, _) => Input::Invalid("usage: /join <room>".into()),
+            (Some("/nick"), Some(nick), None) => Input::Nick(nick.to_string()),
+            (Some("/nick"), _, _) => Input::Invalid("usage: /nick <name>".into()),
+            (Some("/who"), None, None) => Input::Who,
+            (Some("/who"), _, _) => Input::Invalid("usage: /who".into()),
+            (Some(cmd), _, _) => Input::Invalid(format!("unknown command {}", cmd)),
+            (None, _, _) => unreachable!("the line starts with /"),
+        }
+    }
+}
+
+fn validate_nick(nick: &str) -> Result<(), String> {
+    if nick.is_empty() || nick.len() > 32 {
+        Err("nicknames must be 1 to 32 characters".into())
+    } else if nick.contains(char::is_whitespace) {
+        Err("nicknames may not contain spaces".into())
+    } else if nick.starts_with('/') || nick.starts_with('*') {
+        Err("nicknames may not start with / or *".into())
+    } else {
+        Ok(())
+    }
+}
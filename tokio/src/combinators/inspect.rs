@@ -0,0 +1,61 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// The future returned by `FutureCombinators::inspect`
+pub struct Inspect<F, G> {
+    future: F,
+    /// Taken when it's called
+    f: Option<G>,
+}
+
+impl<F, G> Inspect<F, G> {
+    pub(super) fn new(future: F, f: G) -> Inspect<F, G> {
+        Inspect { future, f: Some(f) }
+    }
+}
+
+impl<F, G> Future for Inspect<F, G>
+where
+    F: Future,
+    G: FnOnce(&F::Output),
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<F::Output> {
+        // SAFETY: as for `Map`, only `future` is pinned, and it's never
+        // moved out
+        let this = unsafe { self.get_unchecked_mut() };
+        assert!(this.f.is_some(), "Inspect polled after it completed");
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let output = match future.poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+        let f = this.f.take().unwrap();
+        f(&output);
+        Poll::Ready(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{testing::*, FutureCombinators};
+    use std::{cell::RefCell, task::Poll};
+
+    #[test]
+    fn test_inspect_sees_the_output_once_it_is_ready() {
+        let seen = RefCell::new(Vec::new());
+        let future = countdown(2, 7).inspect(|n| seen.borrow_mut().push(*n));
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert!(seen.borrow().is_empty());
+        assert_eq!(poll_once(future), Poll::Ready(7));
+        assert_eq!(*seen.borrow(), vec![7]);
+    }
+}
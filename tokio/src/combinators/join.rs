@@ -0,0 +1,192 @@
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Runs two futures at once, completing with both outputs once both have
+/// completed
+pub fn join<A: Future, B: Future>(a: A, b: B) -> Join<A, B> {
+    Join {
+        a: MaybeDone::Future(a),
+        b: MaybeDone::Future(b),
+    }
+}
+
+/// Runs any number of futures at once, completing with their outputs in
+/// the same order once all of them have completed
+pub fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> JoinAll<F> {
+    let futures: Box<[_]> = futures.into_iter().map(MaybeDone::Future).collect();
+    JoinAll {
+        futures: futures.into(),
+    }
+}
+
+/// The future returned by `join`
+pub struct Join<A: Future, B: Future> {
+    a: MaybeDone<A>,
+    b: MaybeDone<B>,
+}
+
+/// The future returned by `join_all`
+pub struct JoinAll<F: Future> {
+    /// Pinned on the heap, so `JoinAll` can be moved even once they're
+    /// pinned
+    futures: Pin<Box<[MaybeDone<F>]>>,
+}
+
+/// A future, then its output once it has completed, so that it isn't
+/// polled again while waiting for the others
+enum MaybeDone<F: Future> {
+    Future(F),
+    Done(F::Output),
+    Taken,
+}
+
+impl<F: Future> MaybeDone<F> {
+    /// Polls the future unless it has already completed, returning whether
+    /// it has now
+    fn poll_done(self: Pin<&mut Self>, cx: &mut Context) -> bool {
+        // SAFETY: the future is pinned whenever `self` is, and it's never
+        // moved out. Once it completes, assigning over it drops it in
+        // place. The output isn't pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        if let MaybeDone::Future(ref mut future) = *this {
+            match unsafe { Pin::new_unchecked(future) }.poll(cx) {
+                Poll::Ready(output) => *this = MaybeDone::Done(output),
+                Poll::Pending => return false,
+            }
+        }
+        true
+    }
+
+    fn take_output(self: Pin<&mut Self>) -> F::Output {
+        // SAFETY: only a completed future's output is moved out, and that
+        // isn't pinned
+        let this = unsafe { self.get_unchecked_mut() };
+        if let MaybeDone::Done(_) = *this {
+            if let MaybeDone::Done(output) = mem::replace(this, MaybeDone::Taken) {
+                return output;
+            }
+        }
+        panic!("Join polled after it completed");
+    }
+}
+
+impl<A: Future, B: Future> Future for Join<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // SAFETY: `a` and `b` are pinned whenever `self` is. `Join` never
+        // moves them and has no `Drop` impl.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut a = unsafe { Pin::new_unchecked(&mut this.a) };
+        let mut b = unsafe { Pin::new_unchecked(&mut this.b) };
+
+        // Both are polled, even if the first isn't ready
+        let a_done = a.as_mut().poll_done(cx);
+        let b_done = b.as_mut().poll_done(cx);
+        if a_done && b_done {
+            Poll::Ready((a.take_output(), b.take_output()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let futures = &mut self.get_mut().futures;
+        let mut all_done = true;
+        for i in 0..futures.len() {
+            // SAFETY: the elements of a pinned slice are pinned too, and
+            // they're never moved out
+            let future = unsafe { futures.as_mut().map_unchecked_mut(|futures| &mut futures[i]) };
+            all_done &= future.poll_done(cx);
+        }
+        if !all_done {
+            return Poll::Pending;
+        }
+
+        let outputs = (0..futures.len())
+            .map(|i| unsafe { futures.as_mut().map_unchecked_mut(|futures| &mut futures[i]) }.take_output())
+            .collect();
+        Poll::Ready(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::testing::*;
+
+    #[test]
+    fn test_join_waits_for_both() {
+        let a = countdown(1, "a");
+        let b = countdown(3, 2);
+        let (a_polls, b_polls) = (a.polls.clone(), b.polls.clone());
+        let future = join(a, b);
+        futures::pin_mut!(future);
+
+        for _ in 0..3 {
+            assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        }
+        assert_eq!(poll_once(future), Poll::Ready(("a", 2)));
+        // The finished one wasn't polled again
+        assert_eq!(a_polls.get(), 2);
+        assert_eq!(b_polls.get(), 4);
+    }
+
+    #[test]
+    fn test_join_async_blocks() {
+        let future = join(async { countdown(2, 1).await }, async { 2 });
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready((1, 2)));
+    }
+
+    #[test]
+    fn test_join_all_keeps_the_order() {
+        let futures: Vec<_> = (0..5).map(|n| countdown(5 - n, n)).collect();
+        let polls: Vec<_> = futures.iter().map(|future| future.polls.clone()).collect();
+        let mut future = join_all(futures);
+
+        for _ in 0..5 {
+            assert_eq!(poll_once(Pin::new(&mut future)), Poll::Pending);
+        }
+        assert_eq!(poll_once(Pin::new(&mut future)), Poll::Ready(vec![0, 1, 2, 3, 4]));
+        let polls: Vec<_> = polls.iter().map(|polls| polls.get()).collect();
+        assert_eq!(polls, vec![6, 5, 4, 3, 2]);
+    }
+
+    #[test]
+    fn test_join_all_of_nothing() {
+        let mut future = join_all(Vec::<Countdown<()>>::new());
+        assert_eq!(poll_once(Pin::new(&mut future)), Poll::Ready(vec![]));
+    }
+
+    #[test]
+    fn test_join_all_async_blocks() {
+        let future = join_all((0..3).map(|n| async move { countdown(n, n).await }));
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(vec![0, 1, 2]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Join polled after it completed")]
+    fn test_polled_after_completing() {
+        let future = join(countdown(0, 1), countdown(0, 2));
+        futures::pin_mut!(future);
+
+        let _ = poll_once(future.as_mut());
+        let _ = poll_once(future);
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// The future returned by `FutureCombinators::map`
+pub struct Map<F, G> {
+    future: F,
+    /// Taken when it's called
+    f: Option<G>,
+}
+
+impl<F, G> Map<F, G> {
+    pub(super) fn new(future: F, f: G) -> Map<F, G> {
+        Map { future, f: Some(f) }
+    }
+}
+
+impl<F, G, U> Future for Map<F, G>
+where
+    F: Future,
+    G: FnOnce(F::Output) -> U,
+{
+    type Output = U;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<U> {
+        // SAFETY: `future` is pinned whenever `self` is. It's never moved
+        // out and `Map` has no `Drop` impl that could move it. `f` isn't
+        // pinned, so it's fine to move it out.
+        let this = unsafe { self.get_unchecked_mut() };
+        assert!(this.f.is_some(), "Map polled after it completed");
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let output = match future.poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+        let f = this.f.take().unwrap();
+        Poll::Ready(f(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{testing::*, FutureCombinators};
+    use std::task::Poll;
+
+    #[test]
+    fn test_map() {
+        let future = countdown(0, 2).map(|n| n * 10);
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future), Poll::Ready(20));
+    }
+
+    #[test]
+    fn test_map_waits_for_the_inner_future() {
+        let inner = countdown(3, "hello");
+        let polls = inner.polls.clone();
+        let future = inner.map(str::len);
+        futures::pin_mut!(future);
+
+        for _ in 0..3 {
+            assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        }
+        assert_eq!(poll_once(future), Poll::Ready(5));
+        assert_eq!(polls.get(), 4);
+    }
+
+    #[test]
+    fn test_map_an_async_block() {
+        let future = async { countdown(1, 4).await }.map(|n| n + 1);
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Map polled after it completed")]
+    fn test_polled_after_completing() {
+        let future = countdown(0, 1).map(|n| n);
+        futures::pin_mut!(future);
+
+        let _ = poll_once(future.as_mut());
+        let _ = poll_once(future);
+    }
+}
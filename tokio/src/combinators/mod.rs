@@ -0,0 +1,116 @@
+//! Futures that wrap other futures, grown from the `Display` wrapper in
+//! `hello_future`.
+//!
+//! Each one is written out by hand rather than with `async` blocks, to show
+//! what they do: poll the futures they wrap and pass on `Pending` until
+//! they have what they need. None of them needs the futures it wraps to be
+//! `Unpin`. They project the pin onto their fields themselves, each with a
+//! `SAFETY` comment saying why that's sound.
+//!
+//! They don't depend on a runtime. `timeout` and `retry` take the futures
+//! they wait on from the caller, such as `tokio::time::delay_for`.
+
+mod inspect;
+mod join;
+mod map;
+mod retry;
+mod select;
+mod then;
+mod timeout;
+
+use std::future::Future;
+
+pub use self::{
+    inspect::Inspect,
+    join::{join, join_all, Join, JoinAll},
+    map::Map,
+    retry::{retry, Backoff, Retry},
+    select::{select, Either, Select},
+    then::Then,
+    timeout::{timeout, Elapsed, Timeout},
+};
+
+/// Methods for wrapping a future in one of the combinators
+pub trait FutureCombinators: Future + Sized {
+    /// Passes the output through `f`
+    fn map<G, U>(self, f: G) -> Map<Self, G>
+    where
+        G: FnOnce(Self::Output) -> U,
+    {
+        Map::new(self, f)
+    }
+
+    /// Passes the output to `f`, then runs the future it returns
+    fn then<G, B>(self, f: G) -> Then<Self, B, G>
+    where
+        G: FnOnce(Self::Output) -> B,
+        B: Future,
+    {
+        Then::new(self, f)
+    }
+
+    /// Lets `f` look at the output on its way past
+    fn inspect<G>(self, f: G) -> Inspect<Self, G>
+    where
+        G: FnOnce(&Self::Output),
+    {
+        Inspect::new(self, f)
+    }
+
+    /// Gives up if `delay` completes first
+    fn timeout<D>(self, delay: D) -> Timeout<Self, D>
+    where
+        D: Future<Output = ()>,
+    {
+        timeout(self, delay)
+    }
+}
+
+impl<F: Future> FutureCombinators for F {}
+
+#[cfg(test)]
+mod testing {
+    use std::{
+        cell::Cell,
+        future::Future,
+        pin::Pin,
+        rc::Rc,
+        task::{Context, Poll},
+    };
+    use futures::task::noop_waker_ref;
+
+    /// Polls `future` once, with a waker that does nothing
+    pub fn poll_once<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+        future.poll(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    /// Returns `Pending` a number of times before it completes, counting
+    /// how often it's polled
+    pub struct Countdown<T> {
+        pending: usize,
+        value: Option<T>,
+        pub polls: Rc<Cell<usize>>,
+    }
+
+    pub fn countdown<T>(pending: usize, value: T) -> Countdown<T> {
+        Countdown {
+            pending,
+            value: Some(value),
+            polls: Rc::new(Cell::new(0)),
+        }
+    }
+
+    impl<T: Unpin> Future for Countdown<T> {
+        type Output = T;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+            self.polls.set(self.polls.get() + 1);
+            if self.pending > 0 {
+                self.pending -= 1;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(self.value.take().expect("Countdown polled after it completed"))
+        }
+    }
+}
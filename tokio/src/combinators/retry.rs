@@ -0,0 +1,224 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// How long `retry` waits between attempts, and how many it makes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backoff {
+    /// The delay before the first retry
+    pub initial: Duration,
+    /// How much the delay grows by after each retry
+    pub factor: u32,
+    /// The longest the delay can grow to
+    pub max_delay: Duration,
+    /// How many attempts are made in all, including the first
+    pub max_attempts: u32,
+}
+
+impl Backoff {
+    /// The delay before retry number `retry`, counting from 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.factor
+            .checked_pow(retry)
+            .and_then(|factor| self.initial.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff {
+            initial: Duration::from_millis(10),
+            factor: 2,
+            max_delay: Duration::from_secs(1),
+            max_attempts: 5,
+        }
+    }
+}
+
+/// Runs the future that `make` returns, making a new one and running it
+/// again each time it fails, until it succeeds or `backoff` runs out of
+/// attempts. Then it completes with the last error. `sleep` returns the
+/// future to wait on between attempts, such as `tokio::time::delay_for`.
+///
+/// The first future is made straight away.
+pub fn retry<M, Fut, T, E, S, D>(mut make: M, backoff: Backoff, sleep: S) -> Retry<M, Fut, S, D>
+where
+    M: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    S: FnMut(Duration) -> D,
+    D: Future<Output = ()>,
+{
+    Retry {
+        state: State::Running(make()),
+        make,
+        sleep,
+        backoff,
+        attempts: 0,
+    }
+}
+
+/// The future returned by `retry`
+pub struct Retry<M, Fut, S, D> {
+    state: State<Fut, D>,
+    make: M,
+    sleep: S,
+    backoff: Backoff,
+    /// How many attempts have failed
+    attempts: u32,
+}
+
+enum State<Fut, D> {
+    Running(Fut),
+    Waiting(D),
+    Done,
+}
+
+impl<M, Fut, T, E, S, D> Future for Retry<M, Fut, S, D>
+where
+    M: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    S: FnMut(Duration) -> D,
+    D: Future<Output = ()>,
+{
+    type Output = Result<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // SAFETY: as for `Then`, only the future `state` holds is pinned,
+        // and it's never moved out. Moving on to the next state assigns
+        // over the old one, which drops it in place. The other fields are
+        // never pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match this.state {
+                State::Running(ref mut attempt) => {
+                    let attempt = unsafe { Pin::new_unchecked(attempt) };
+                    let error = match attempt.poll(cx) {
+                        Poll::Ready(Ok(output)) => {
+                            this.state = State::Done;
+                            return Poll::Ready(Ok(output));
+                        }
+                        Poll::Ready(Err(error)) => error,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    this.attempts += 1;
+                    if this.attempts >= this.backoff.max_attempts {
+                        this.state = State::Done;
+                        return Poll::Ready(Err(error));
+                    }
+                    let delay = this.backoff.delay(this.attempts - 1);
+                    this.state = State::Waiting((this.sleep)(delay));
+                }
+                State::Waiting(ref mut delay) => {
+                    let delay = unsafe { Pin::new_unchecked(delay) };
+                    if delay.poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    this.state = State::Running((this.make)());
+                }
+                State::Done => panic!("Retry polled after it completed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::testing::*;
+    use std::cell::RefCell;
+
+    fn backoff(max_attempts: u32) -> Backoff {
+        Backoff {
+            max_attempts,
+            ..Backoff::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff_grows_up_to_the_max() {
+        let backoff = Backoff {
+            max_delay: Duration::from_millis(50),
+            ..Backoff::default()
+        };
+        let delays: Vec<_> = (0..5).map(|retry| backoff.delay(retry).as_millis()).collect();
+        assert_eq!(delays, vec![10, 20, 40, 50, 50]);
+        assert_eq!(backoff.delay(100), backoff.max_delay);
+    }
+
+    #[test]
+    fn test_retries_until_it_succeeds() {
+        let mut results = vec![Ok("done"), Err("again"), Err("first")];
+        let slept = RefCell::new(Vec::new());
+        let future = retry(
+            || countdown(1, results.pop().unwrap()),
+            backoff(5),
+            |delay| {
+                slept.borrow_mut().push(delay);
+                countdown(1, ())
+            },
+        );
+        futures::pin_mut!(future);
+
+        // Each attempt and each sleep is pending once
+        for _ in 0..5 {
+            assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        }
+        assert_eq!(poll_once(future), Poll::Ready(Ok("done")));
+        let expected = vec![Duration::from_millis(10), Duration::from_millis(20)];
+        assert_eq!(*slept.borrow(), expected);
+    }
+
+    #[test]
+    fn test_gives_up_with_the_last_error() {
+        let mut attempts = 0;
+        let future = retry(
+            || {
+                attempts += 1;
+                countdown(0, Err::<(), _>(attempts))
+            },
+            backoff(3),
+            |_| countdown(2, ()),
+        );
+        futures::pin_mut!(future);
+
+        for _ in 0..4 {
+            assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        }
+        assert_eq!(poll_once(future), Poll::Ready(Err(3)));
+    }
+
+    #[test]
+    fn test_no_sleep_after_the_first_success() {
+        let future = retry(|| countdown(2, Ok::<_, ()>(1)), backoff(3), |_| -> Countdown<()> {
+            panic!("slept after succeeding")
+        });
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(Ok(1)));
+    }
+
+    #[tokio::test]
+    async fn test_with_tokio_delay() {
+        let mut attempts = 0;
+        let backoff = Backoff {
+            initial: Duration::from_millis(1),
+            ..Backoff::default()
+        };
+        let result = retry(
+            || {
+                attempts += 1;
+                let result = if attempts < 3 { Err(attempts) } else { Ok(attempts) };
+                async move { result }
+            },
+            backoff,
+            tokio::time::delay_for,
+        );
+        assert_eq!(result.await, Ok(3));
+    }
+}
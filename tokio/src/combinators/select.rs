@@ -0,0 +1,97 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Runs two futures at once, completing with the output of whichever
+/// completes first. The other is dropped along with the `Select`. If both
+/// are ready at once, `a` wins.
+pub fn select<A: Future, B: Future>(a: A, b: B) -> Select<A, B> {
+    Select { a, b, done: false }
+}
+
+/// The future returned by `select`
+pub struct Select<A, B> {
+    a: A,
+    b: B,
+    done: bool,
+}
+
+/// The output of the future that won a `select`
+#[derive(Debug, PartialEq)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // SAFETY: `a` and `b` are pinned whenever `self` is. `Select` never
+        // moves them and has no `Drop` impl.
+        let this = unsafe { self.get_unchecked_mut() };
+        assert!(!this.done, "Select polled after it completed");
+        let a = unsafe { Pin::new_unchecked(&mut this.a) };
+        let b = unsafe { Pin::new_unchecked(&mut this.b) };
+
+        let output = if let Poll::Ready(output) = a.poll(cx) {
+            Either::Left(output)
+        } else if let Poll::Ready(output) = b.poll(cx) {
+            Either::Right(output)
+        } else {
+            return Poll::Pending;
+        };
+        this.done = true;
+        Poll::Ready(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::testing::*;
+
+    #[test]
+    fn test_select_the_first_to_finish() {
+        let a = countdown(3, "a");
+        let b = countdown(1, "b");
+        let a_polls = a.polls.clone();
+        let future = select(a, b);
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(Either::Right("b")));
+        assert_eq!(a_polls.get(), 2);
+    }
+
+    #[test]
+    fn test_a_wins_a_tie() {
+        let future = select(countdown(2, 1), countdown(2, 2));
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(Either::Left(1)));
+    }
+
+    #[test]
+    fn test_select_async_blocks() {
+        let future = select(futures::future::pending::<()>(), async { countdown(1, 5).await });
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(Either::Right(5)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Select polled after it completed")]
+    fn test_polled_after_completing() {
+        let future = select(countdown(0, 1), countdown(0, 2));
+        futures::pin_mut!(future);
+
+        let _ = poll_once(future.as_mut());
+        let _ = poll_once(future);
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// The future returned by `FutureCombinators::then`
+pub struct Then<A, B, G> {
+    state: State<A, B, G>,
+}
+
+enum State<A, B, G> {
+    First(A, Option<G>),
+    Second(B),
+    Done,
+}
+
+impl<A, B, G> Then<A, B, G> {
+    pub(super) fn new(first: A, f: G) -> Then<A, B, G> {
+        Then {
+            state: State::First(first, Some(f)),
+        }
+    }
+}
+
+impl<A, B, G> Future for Then<A, B, G>
+where
+    A: Future,
+    B: Future,
+    G: FnOnce(A::Output) -> B,
+{
+    type Output = B::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<B::Output> {
+        // SAFETY: whichever future `state` holds is pinned whenever `self`
+        // is. Neither is ever moved out. Moving on to the next state
+        // assigns over the old one, which drops it in place, as `Pin::set`
+        // does. `Then` has no `Drop` impl.
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match this.state {
+                State::First(ref mut first, ref mut f) => {
+                    let first = unsafe { Pin::new_unchecked(first) };
+                    let output = match first.poll(cx) {
+                        Poll::Ready(output) => output,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    let f = f.take().unwrap();
+                    this.state = State::Second(f(output));
+                }
+                State::Second(ref mut second) => {
+                    let second = unsafe { Pin::new_unchecked(second) };
+                    let output = match second.poll(cx) {
+                        Poll::Ready(output) => output,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    this.state = State::Done;
+                    return Poll::Ready(output);
+                }
+                State::Done => panic!("Then polled after it completed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{testing::*, FutureCombinators};
+    use std::{cell::Cell, rc::Rc, task::Poll};
+
+    #[test]
+    fn test_then_runs_the_second_future_straight_away() {
+        let future = countdown(0, 3).then(|n| countdown(0, n * 2));
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future), Poll::Ready(6));
+    }
+
+    #[test]
+    fn test_then_waits_for_both_futures() {
+        let first = countdown(2, 3);
+        let first_polls = first.polls.clone();
+        let second_polls = Rc::new(Cell::new(0));
+        let future = {
+            let second_polls = second_polls.clone();
+            first.then(move |n| {
+                let mut second = countdown(3, n + 1);
+                second.polls = second_polls;
+                second
+            })
+        };
+        futures::pin_mut!(future);
+
+        for _ in 0..5 {
+            assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        }
+        assert_eq!(poll_once(future), Poll::Ready(4));
+        assert_eq!(first_polls.get(), 3);
+        assert_eq!(second_polls.get(), 4);
+    }
+
+    #[test]
+    fn test_then_with_async_blocks() {
+        let future = async { countdown(1, 10).await }
+            .then(|n| async move { countdown(1, n).await + 1 });
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(11));
+    }
+
+    #[test]
+    #[should_panic(expected = "Then polled after it completed")]
+    fn test_polled_after_completing() {
+        let future = countdown(0, 1).then(|n| countdown(0, n));
+        futures::pin_mut!(future);
+
+        let _ = poll_once(future.as_mut());
+        let _ = poll_once(future);
+    }
+}
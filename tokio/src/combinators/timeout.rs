@@ -0,0 +1,105 @@
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Runs `future`, giving up on it if `delay` completes first. On tokio the
+/// delay would be `tokio::time::delay_for(duration)`.
+pub fn timeout<F: Future, D: Future<Output = ()>>(future: F, delay: D) -> Timeout<F, D> {
+    Timeout { future, delay }
+}
+
+/// The future returned by `timeout`
+pub struct Timeout<F, D> {
+    future: F,
+    delay: D,
+}
+
+/// The error from a `Timeout` whose delay completed first
+#[derive(Debug, PartialEq)]
+pub struct Elapsed;
+
+impl<F, D> Future for Timeout<F, D>
+where
+    F: Future,
+    D: Future<Output = ()>,
+{
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        // SAFETY: `future` and `delay` are pinned whenever `self` is.
+        // `Timeout` never moves them and has no `Drop` impl.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let delay = unsafe { Pin::new_unchecked(&mut this.delay) };
+
+        // The future goes first, so one that's ready in time isn't lost
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match delay.poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out")
+    }
+}
+
+impl Error for Elapsed {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{testing::*, FutureCombinators};
+
+    #[test]
+    fn test_finishes_in_time() {
+        let future = countdown(2, "done").timeout(countdown(3, ()));
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(Ok("done")));
+    }
+
+    #[test]
+    fn test_times_out() {
+        let inner = countdown(10, "done");
+        let polls = inner.polls.clone();
+        let future = timeout(inner, countdown(2, ()));
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(Err(Elapsed)));
+        assert_eq!(polls.get(), 3);
+    }
+
+    #[test]
+    fn test_ready_at_the_deadline_wins() {
+        let future = timeout(countdown(1, 1), countdown(1, ()));
+        futures::pin_mut!(future);
+
+        assert_eq!(poll_once(future.as_mut()), Poll::Pending);
+        assert_eq!(poll_once(future), Poll::Ready(Ok(1)));
+    }
+
+    #[tokio::test]
+    async fn test_with_tokio_delay() {
+        use std::time::Duration;
+        use tokio::time::delay_for;
+
+        let slow = delay_for(Duration::from_secs(10)).timeout(delay_for(Duration::from_millis(10)));
+        assert_eq!(slow.await, Err(Elapsed));
+        let quick = async { 1 }.timeout(delay_for(Duration::from_secs(10)));
+        assert_eq!(quick.await, Ok(1));
+    }
+}
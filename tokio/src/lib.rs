@@ -0,0 +1,8 @@
+pub mod chat;
+pub mod combinators;
+pub mod listen;
+pub mod proxy;
+pub mod server;
+pub mod tinydb;
+pub mod tls;
+pub mod udp;
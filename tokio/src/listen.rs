@@ -0,0 +1,235 @@
+//! Lets the servers listen, and clients connect, on either TCP or a Unix
+//! domain socket
+
+use std::{
+    fmt, fs, io,
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net,
+    },
+    path::{Path, PathBuf},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream, UnixListener, UnixStream},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenAddr {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// A client's connection to a server over either transport
+pub trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+impl ListenAddr {
+    /// `unix:/path/to/socket` is a Unix socket, anything else a TCP address
+    pub fn parse(input: &str) -> ListenAddr {
+        match input.strip_prefix("unix:") {
+            Some(path) => ListenAddr::Unix(PathBuf::from(path)),
+            None => ListenAddr::Tcp(input.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ListenAddr::Tcp(ref addr) => write!(f, "{}", addr),
+            ListenAddr::Unix(ref path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl Listener {
+    /// Binds `addr`, `unix_mode` sets the permissions of a Unix socket file
+    pub async fn bind(addr: &ListenAddr, unix_mode: Option<u32>) -> io::Result<Listener> {
+        match *addr {
+            ListenAddr::Tcp(ref addr) => Ok(Listener::Tcp(TcpListener::bind(addr).await?)),
+            ListenAddr::Unix(ref path) => Ok(Listener::Unix(bind_unix(path, unix_mode)?)),
+        }
+    }
+
+    pub async fn accept(&mut self) -> io::Result<Box<dyn Connection>> {
+        match *self {
+            Listener::Tcp(ref mut listener) => {
+                let socket = listener.accept().await?.0;
+                set_nodelay(&socket);
+                Ok(Box::new(socket))
+            }
+            Listener::Unix(ref mut listener) => Ok(Box::new(listener.accept().await?.0)),
+        }
+    }
+}
+
+/// Each response is written as soon as it's ready, so a client waiting on
+/// several shouldn't wait on Nagle as well. The connection works without
+/// it, just slower, so failing is only worth a warning.
+fn set_nodelay(socket: &TcpStream) {
+    if let Err(e) = socket.set_nodelay(true) {
+        eprintln!("couldn't turn off Nagle's algorithm for a connection: {}", e);
+    }
+}
+
+/// Connects to a server listening on `addr`
+pub async fn connect(addr: &ListenAddr) -> io::Result<Box<dyn Connection>> {
+    match *addr {
+        ListenAddr::Tcp(ref addr) => Ok(Box::new(TcpStream::connect(addr).await?)),
+        ListenAddr::Unix(ref path) => Ok(Box::new(UnixStream::connect(path).await?)),
+    }
+}
+
+/// Parses an octal file mode such as `660` or `0660`
+pub fn parse_mode(input: &str) -> Result<u32, String> {
+    u32::from_str_radix(input, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("invalid socket mode: {}", input))
+}
+
+/// Binds a Unix socket, removing a stale socket file left behind by a
+/// previous run first.
+///
+/// The mode is applied after binding, so there is a brief window where the
+/// socket has the permissions given by the umask.
+pub fn bind_unix(path: &Path, mode: Option<u32>) -> io::Result<UnixListener> {
+    remove_stale_socket(path)?;
+    let listener = UnixListener::bind(path)?;
+    if let Some(mode) = mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    Ok(listener)
+}
+
+/// A socket file nobody is accepting connections on is stale. Anything else
+/// at `path`, a live socket or a file that isn't a socket, is left alone.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+
+    match net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another server", path.display()),
+        )),
+        Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listen_addr() {
+        assert_eq!(
+            ListenAddr::parse("127.0.0.1:8080"),
+            ListenAddr::Tcp("127.0.0.1:8080".into())
+        );
+        assert_eq!(
+            ListenAddr::parse("unix:/tmp/db.sock"),
+            ListenAddr::Unix("/tmp/db.sock".into())
+        );
+        assert_eq!(ListenAddr::parse("unix:/tmp/db.sock").to_string(), "unix:/tmp/db.sock");
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("660"), Ok(0o660));
+        assert_eq!(parse_mode("0600"), Ok(0o600));
+        assert!(parse_mode("888").is_err());
+        assert!(parse_mode("7777").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_nodelay() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+        assert!(!socket.nodelay().unwrap());
+
+        set_nodelay(&socket);
+        assert!(socket.nodelay().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix_sets_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sock");
+
+        let _listener = bind_unix(&path, Some(0o600)).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix_removes_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sock");
+
+        // Dropping a listener leaves its socket file behind
+        drop(net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let _listener = bind_unix(&path, None).unwrap();
+        net::UnixStream::connect(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = ListenAddr::Unix(dir.path().join("test.sock"));
+
+        let mut listener = match Listener::bind(&addr, None).await.unwrap() {
+            Listener::Unix(listener) => listener,
+            Listener::Tcp(_) => panic!("expected a Unix listener"),
+        };
+
+        let (client, server) = futures::join!(connect(&addr), listener.accept());
+        client.unwrap();
+        server.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix_refuses_live_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sock");
+
+        let _live = net::UnixListener::bind(&path).unwrap();
+
+        let err = bind_unix(&path, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix_refuses_to_remove_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not-a-socket");
+        fs::write(&path, "precious").unwrap();
+
+        let err = bind_unix(&path, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "precious");
+    }
+}
@@ -0,0 +1,376 @@
+//! Forwards connections to an upstream server, optionally logging the
+//! traffic and simulating a slow network along the way
+
+use std::{fmt::Write as _, io, time::Duration};
+use futures::future::{self, Either};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+    time::{delay_until, Instant},
+};
+use crate::listen::{self, ListenAddr};
+
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// How many chunks can be waiting out the injected latency at once
+const CHUNKS_IN_FLIGHT: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub upstream: ListenAddr,
+    pub log: Option<LogMode>,
+    /// Added to every chunk of data in both directions
+    pub latency: Duration,
+    /// Bytes per second in each direction, unlimited when `None`
+    pub bandwidth: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogMode {
+    /// `hexdump -C` style, for binary protocols
+    Hex,
+    /// One log line per line of text
+    Lines,
+}
+
+/// Bytes forwarded over one connection
+#[derive(Debug, Default, PartialEq)]
+pub struct Counters {
+    pub to_upstream: u64,
+    pub to_client: u64,
+}
+
+impl LogMode {
+    pub fn parse(input: &str) -> Result<LogMode, String> {
+        match input {
+            "hex" => Ok(LogMode::Hex),
+            "lines" => Ok(LogMode::Lines),
+            mode => Err(format!("unknown log mode: {}", mode)),
+        }
+    }
+}
+
+/// Connects to the upstream and copies both directions at once until both
+/// sides have closed. `id` labels the connection in the traffic log. The
+/// bytes forwarded are counted even if one of the directions fails.
+pub async fn proxy<S>(client: S, config: &Config, id: u64) -> (Counters, io::Result<()>)
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let upstream = match listen::connect(&config.upstream).await {
+        Ok(upstream) => upstream,
+        Err(e) => return (Counters::default(), Err(e)),
+    };
+    let (client_reader, client_writer) = tokio::io::split(client);
+    let (upstream_reader, upstream_writer) = tokio::io::split(upstream);
+
+    let ((to_upstream, sent), (to_client, received)) = futures::join!(
+        pump(client_reader, upstream_writer, config, format!("[{} >]", id)),
+        pump(upstream_reader, client_writer, config, format!("[{} <]", id)),
+    );
+    (Counters { to_upstream, to_client }, sent.and(received))
+}
+
+/// Copies one direction, returning the number of bytes copied along with
+/// whether it ended cleanly. The reader and writer are joined by a channel
+/// acting as a delay line, so latency delays each chunk without limiting
+/// how many can be on their way.
+async fn pump<R, W>(
+    mut reader: R,
+    mut writer: W,
+    config: &Config,
+    label: String,
+) -> (u64, io::Result<()>)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (mut sender, mut receiver) = mpsc::channel::<(Instant, Vec<u8>)>(CHUNKS_IN_FLIGHT);
+
+    // Keep chunks small when throttled so the data trickles out evenly
+    let chunk_size = match config.bandwidth {
+        Some(bandwidth) => (bandwidth as usize / 20).clamp(1, BUFFER_SIZE),
+        None => BUFFER_SIZE,
+    };
+
+    let read = async move {
+        let mut buf = vec![0; chunk_size];
+        loop {
+            let n = reader.read(&mut buf).await?;
+            // Dropping the sender at the end of the stream stops the writer,
+            // and a failed send means the writer has already stopped
+            if n == 0 || sender.send((Instant::now(), buf[..n].to_vec())).await.is_err() {
+                return Ok::<_, io::Error>(());
+            }
+        }
+    };
+
+    let write = async move {
+        let mut total = 0;
+        let mut throttle = Throttle::new(config.bandwidth);
+        let mut logger = Logger::new(config.log, label);
+
+        let result = async {
+            while let Some((read_at, chunk)) = receiver.recv().await {
+                delay_until(read_at + config.latency).await;
+                throttle.wait(chunk.len()).await;
+                for line in logger.log(&chunk) {
+                    println!("{}", line);
+                }
+                writer.write_all(&chunk).await?;
+                total += chunk.len() as u64;
+            }
+
+            for line in logger.finish() {
+                println!("{}", line);
+            }
+            // Pass the end of the stream on, the other direction may carry on
+            writer.shutdown().await
+        }
+        .await;
+        (total, result)
+    };
+
+    futures::pin_mut!(read, write);
+    match future::select(read, write).await {
+        // The writer still has the chunks already read to pass on
+        Either::Left((read_result, write)) => {
+            let (total, write_result) = write.await;
+            (total, read_result.and(write_result))
+        }
+        // With nowhere left to write to, there's no point waiting for the
+        // reader, which might not see anything more for a long time
+        Either::Right(((total, write_result), _)) => (total, write_result),
+    }
+}
+
+/// Spaces chunks out so that on average no more than `bytes_per_sec` go by
+struct Throttle {
+    bytes_per_sec: Option<u64>,
+    next: Option<Instant>,
+}
+
+impl Throttle {
+    fn new(bytes_per_sec: Option<u64>) -> Throttle {
+        Throttle { bytes_per_sec, next: None }
+    }
+
+    async fn wait(&mut self, len: usize) {
+        let bytes_per_sec = match self.bytes_per_sec {
+            Some(bytes_per_sec) => bytes_per_sec,
+            None => return,
+        };
+
+        let now = Instant::now();
+        let start = self.next.map_or(now, |next| next.max(now));
+        delay_until(start).await;
+        self.next = Some(start + Duration::from_secs_f64(len as f64 / bytes_per_sec as f64));
+    }
+}
+
+/// Turns the chunks going one way into log lines
+struct Logger {
+    mode: Option<LogMode>,
+    label: String,
+    /// In lines mode, the start of a line still waiting for its newline
+    partial: Vec<u8>,
+}
+
+impl Logger {
+    fn new(mode: Option<LogMode>, label: String) -> Logger {
+        Logger {
+            mode,
+            label,
+            partial: Vec::new(),
+        }
+    }
+
+    fn log(&mut self, chunk: &[u8]) -> Vec<String> {
+        match self.mode {
+            None => Vec::new(),
+            Some(LogMode::Hex) => {
+                let mut lines = vec![format!("{} {} bytes", self.label, chunk.len())];
+                lines.extend(hex_dump(chunk));
+                lines
+            }
+            Some(LogMode::Lines) => {
+                self.partial.extend_from_slice(chunk);
+                let mut lines = Vec::new();
+                while let Some(end) = self.partial.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = self.partial.drain(..=end).collect();
+                    lines.push(self.line(&line));
+                }
+                lines
+            }
+        }
+    }
+
+    /// Logs whatever is left of an unterminated last line
+    fn finish(&mut self) -> Vec<String> {
+        if self.partial.is_empty() {
+            return Vec::new();
+        }
+        let rest = std::mem::take(&mut self.partial);
+        vec![self.line(&rest)]
+    }
+
+    fn line(&self, bytes: &[u8]) -> String {
+        let text = String::from_utf8_lossy(bytes);
+        format!("{} {}", self.label, text.trim_end_matches(['\r', '\n']))
+    }
+}
+
+/// Formats bytes like `hexdump -C`, sixteen to a line
+pub fn hex_dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(n, row)| {
+            let mut line = format!("{:08x} ", n * 16);
+            for i in 0..16 {
+                if i == 8 {
+                    line.push(' ');
+                }
+                match row.get(i) {
+                    Some(byte) => write!(line, " {:02x}", byte).unwrap(),
+                    None => line.push_str("   "),
+                }
+            }
+            let text: String = row
+                .iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect();
+            write!(line, "  |{}|", text).unwrap();
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use tokio::time::timeout;
+
+    /// Reads the chunks it's given, then waits forever for more
+    struct Stalling(Vec<&'static [u8]>);
+
+    impl AsyncRead for Stalling {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.0.is_empty() {
+                return Poll::Pending;
+            }
+            let chunk = self.0.remove(0);
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Poll::Ready(Ok(chunk.len()))
+        }
+    }
+
+    /// Takes `room` bytes, then fails
+    struct Failing {
+        room: usize,
+    }
+
+    impl AsyncWrite for Failing {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.room == 0 {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+            let n = buf.len().min(self.room);
+            self.room -= n;
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pump_stops_when_the_writer_fails() {
+        let config = Config {
+            upstream: ListenAddr::Tcp("unused".into()),
+            log: None,
+            latency: Duration::from_millis(0),
+            bandwidth: None,
+        };
+        let reader = Stalling(vec![b"hello", b"world"]);
+
+        let pumped = pump(reader, Failing { room: 5 }, &config, "[1 >]".into());
+        let (total, result) = timeout(Duration::from_secs(5), pumped).await.expect("pump hung");
+
+        assert_eq!(total, 5);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(
+            hex_dump(b"hello world\n"),
+            vec!["00000000  68 65 6c 6c 6f 20 77 6f  72 6c 64 0a              |hello world.|"]
+        );
+        assert_eq!(
+            hex_dump(b"0123456789abcdef\x00\xff"),
+            vec![
+                "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|",
+                "00000010  00 ff                                             |..|",
+            ]
+        );
+        assert!(hex_dump(b"").is_empty());
+    }
+
+    #[test]
+    fn test_lines_mode_joins_lines_split_across_chunks() {
+        let mut logger = Logger::new(Some(LogMode::Lines), "[1 >]".into());
+
+        assert_eq!(logger.log(b"GET fo"), Vec::<String>::new());
+        assert_eq!(logger.log(b"o\r\nSET a b\nGE"), vec!["[1 >] GET foo", "[1 >] SET a b"]);
+        assert_eq!(logger.finish(), vec!["[1 >] GE"]);
+        assert_eq!(logger.finish(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_hex_mode_logs_every_chunk() {
+        let mut logger = Logger::new(Some(LogMode::Hex), "[2 <]".into());
+
+        assert_eq!(
+            logger.log(b"hi"),
+            vec![
+                "[2 <] 2 bytes",
+                "00000000  68 69                                             |hi|",
+            ]
+        );
+        assert_eq!(logger.finish(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_no_logging() {
+        let mut logger = Logger::new(None, "[1 >]".into());
+
+        assert!(logger.log(b"line\n").is_empty());
+        assert!(logger.log(b"partial").is_empty());
+        assert!(logger.finish().is_empty());
+    }
+
+    #[test]
+    fn test_log_mode_parse() {
+        assert_eq!(LogMode::parse("hex"), Ok(LogMode::Hex));
+        assert_eq!(LogMode::parse("lines"), Ok(LogMode::Lines));
+        assert!(LogMode::parse("pcap").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! The accept loop shared by the servers: it numbers connections, limits how
+//! many are open at once, survives accept errors and shuts down gracefully
+
+use std::{
+    future::Future,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use futures::future::{self, Either};
+use tokio::{
+    sync::Semaphore,
+    time::{delay_for, timeout},
+};
+use tokio_rustls::TlsAcceptor;
+use crate::listen::{Connection, ListenAddr, Listener};
+
+pub const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Clients that haven't finished the TLS handshake by then are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Serves one connection. Closures taking the connection and its id are
+/// handlers, so most servers don't need to implement this themselves.
+pub trait Handler: Send + Sync + 'static {
+    type Future: Future<Output = ()> + Send + 'static;
+
+    fn handle(&self, socket: Box<dyn Connection>, id: u64) -> Self::Future;
+}
+
+impl<F, Fut> Handler for F
+where
+    F: Fn(Box<dyn Connection>, u64) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    type Future = Fut;
+
+    fn handle(&self, socket: Box<dyn Connection>, id: u64) -> Fut {
+        self(socket, id)
+    }
+}
+
+pub struct Server {
+    listeners: Vec<Listener>,
+    max_connections: usize,
+    grace_period: Duration,
+    tls: Option<TlsAcceptor>,
+}
+
+impl Server {
+    pub fn new(listeners: Vec<Listener>) -> Server {
+        Server {
+            listeners,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            tls: None,
+        }
+    }
+
+    /// Binds every address, `unix_mode` as for `Listener::bind`
+    pub async fn bind(addrs: &[ListenAddr], unix_mode: Option<u32>) -> io::Result<Server> {
+        let mut listeners = Vec::new();
+        for addr in addrs {
+            listeners.push(Listener::bind(addr, unix_mode).await?);
+        }
+        Ok(Server::new(listeners))
+    }
+
+    /// Once this many connections are open, new ones aren't served until
+    /// one closes. Each listener accepts one of them and holds it while it
+    /// waits, the rest wait in the listen backlog.
+    pub fn max_connections(mut self, max_connections: usize) -> Server {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// How long shutting down waits for open connections to finish
+    pub fn grace_period(mut self, grace_period: Duration) -> Server {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Makes every connection start with a TLS handshake, the handler only
+    /// sees the decrypted stream
+    pub fn tls(mut self, acceptor: TlsAcceptor) -> Server {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    /// Accepts connections on every listener, spawning a task for each,
+    /// until `shutdown` completes. Then it stops accepting and waits out
+    /// the grace period for open connections, returning how many were
+    /// still open at the end of it.
+    pub async fn run<H, F>(self, handler: H, shutdown: F) -> usize
+    where
+        H: Handler,
+        F: Future<Output = ()>,
+    {
+        let Server {
+            listeners,
+            max_connections,
+            grace_period,
+            tls,
+        } = self;
+        let handler = Arc::new(handler);
+        let limit = Arc::new(Semaphore::new(max_connections));
+        let next_id = Arc::new(AtomicU64::new(1));
+
+        let accepting = future::join_all(listeners.into_iter().map(|listener| {
+            accept_loop(listener, handler.clone(), tls.clone(), limit.clone(), next_id.clone())
+        }));
+
+        // The accept loops only end by being dropped, which closes the
+        // listeners and drops any connection still waiting for a permit
+        futures::pin_mut!(shutdown);
+        match future::select(Box::pin(accepting), shutdown).await {
+            Either::Left(_) => unreachable!("accept loops never finish"),
+            Either::Right(((), accepting)) => drop(accepting),
+        }
+
+        // Every permit back means every connection has closed
+        let mut closed = 0;
+        let _ = timeout(grace_period, async {
+            while closed < max_connections {
+                limit.acquire().await.forget();
+                closed += 1;
+            }
+        })
+        .await;
+        max_connections - closed
+    }
+}
+
+async fn accept_loop<H: Handler>(
+    mut listener: Listener,
+    handler: Arc<H>,
+    tls: Option<TlsAcceptor>,
+    limit: Arc<Semaphore>,
+    next_id: Arc<AtomicU64>,
+) {
+    let mut backoff = Backoff::new();
+    loop {
+        match listener.accept().await {
+            Ok(socket) => {
+                backoff.reset();
+                // Only waits for a permit once there's a connection, so an
+                // idle listener doesn't hold one that the others could use.
+                // Until then this connection is open but not served.
+                let permit = limit.clone().acquire_owned().await;
+                let id = next_id.fetch_add(1, Ordering::Relaxed);
+                let handler = handler.clone();
+                let tls = tls.clone();
+                // The handshake happens in the connection's own task so a
+                // slow client can't hold up the others
+                tokio::spawn(async move {
+                    let socket = match tls {
+                        Some(acceptor) => match handshake(&acceptor, socket, id).await {
+                            Some(socket) => socket,
+                            None => return,
+                        },
+                        None => socket,
+                    };
+                    handler.handle(socket, id).await;
+                    drop(permit);
+                });
+            }
+            // Errors such as running out of file descriptors won't go away
+            // by retrying straight away
+            Err(e) => {
+                let delay = backoff.next();
+                eprintln!("error accepting connection, retrying in {:?}: {}", delay, e);
+                delay_for(delay).await;
+            }
+        }
+    }
+}
+
+async fn handshake(
+    acceptor: &TlsAcceptor,
+    socket: Box<dyn Connection>,
+    id: u64,
+) -> Option<Box<dyn Connection>> {
+    match timeout(HANDSHAKE_TIMEOUT, acceptor.accept(socket)).await {
+        Ok(Ok(stream)) => Some(Box::new(stream)),
+        Ok(Err(e)) => {
+            eprintln!("TLS handshake failed on connection {}: {}", id, e);
+            None
+        }
+        Err(_) => {
+            eprintln!("TLS handshake timed out on connection {}", id);
+            None
+        }
+    }
+}
+
+/// Parses a connection limit, which must be at least 1
+pub fn parse_max_connections(input: &str) -> Result<usize, String> {
+    input
+        .parse()
+        .ok()
+        .filter(|max| *max > 0)
+        .ok_or_else(|| format!("invalid connection limit: {}", input))
+}
+
+/// Doubles the delay after each consecutive failure, up to a limit
+struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new() -> Backoff {
+        Backoff { delay: MIN_BACKOFF }
+    }
+
+    fn next(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (delay * 2).min(MAX_BACKOFF);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.delay = MIN_BACKOFF;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_limit() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<u64> = (0..9).map(|_| backoff.next().as_millis() as u64).collect();
+        assert_eq!(delays, vec![10, 20, 40, 80, 160, 320, 640, 1000, 1000]);
+
+        backoff.reset();
+        assert_eq!(backoff.next(), MIN_BACKOFF);
+    }
+
+    #[test]
+    fn test_parse_max_connections() {
+        assert_eq!(parse_max_connections("1"), Ok(1));
+        assert_eq!(parse_max_connections("500"), Ok(500));
+        assert!(parse_max_connections("0").is_err());
+        assert!(parse_max_connections("-1").is_err());
+        assert!(parse_max_connections("many").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Users, passwords and the commands and keys each user may access
+
+use std::{collections::HashMap, sync::OnceLock};
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand_core::OsRng;
+use super::Command;
+
+/// Users and their permissions, loaded from the config file
+pub struct Acl {
+    users: HashMap<String, User>,
+}
+
+struct User {
+    /// Argon2 hash in PHC string format
+    password_hash: String,
+    grants: Vec<Grant>,
+}
+
+/// Permission to run some commands against the keys matching a pattern
+struct Grant {
+    commands: Vec<Command>,
+    keys: KeyPattern,
+}
+
+#[derive(Debug, PartialEq)]
+enum KeyPattern {
+    /// `*` matches every key
+    Any,
+    /// `metrics:*` matches every key starting with `metrics:`
+    Prefix(String),
+    /// Anything else must match the key exactly
+    Exact(String),
+}
+
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("could not hash password: {}", e))
+}
+
+/// A hash, with the same parameters as `hash_password`, for unknown users to
+/// be checked against
+fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| hash_password("no such user").unwrap())
+}
+
+impl Acl {
+    /// Parses a config file made up of lines like these:
+    ///
+    /// ```text
+    /// # user <name> <argon2 hash from `tinydb hash-password`>
+    /// user alice $argon2id$v=19$m=19456,t=2,p=1$...
+    /// # grant <name> <comma separated commands> <key pattern>
+    /// grant alice GET,SET *
+    /// grant alice GET metrics:*
+    /// ```
+    ///
+    /// Blank lines and lines starting with `#` are ignored. A user with no
+    /// grants can authenticate but not run any commands.
+    pub fn parse(input: &str) -> Result<Acl, String> {
+        let mut users = HashMap::new();
+
+        for (n, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            Acl::parse_line(&mut users, line)
+                .map_err(|e| format!("config line {}: {}", n + 1, e))?;
+        }
+
+        // Made now, so the first unknown user isn't slower than the rest
+        dummy_hash();
+        Ok(Acl { users })
+    }
+
+    fn parse_line(users: &mut HashMap<String, User>, line: &str) -> Result<(), String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["user", name, hash] => {
+                PasswordHash::new(hash)
+                    .map_err(|e| format!("invalid password hash for {}: {}", name, e))?;
+                let user = User {
+                    password_hash: hash.to_string(),
+                    grants: Vec::new(),
+                };
+                match users.insert(name.to_string(), user) {
+                    Some(_) => Err(format!("user {} is defined twice", name)),
+                    None => Ok(()),
+                }
+            }
+            ["grant", name, commands, keys] => {
+                let commands = commands
+                    .split(',')
+                    .map(Command::parse)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let grant = Grant {
+                    commands,
+                    keys: KeyPattern::parse(keys),
+                };
+                let user = users
+                    .get_mut(*name)
+                    .ok_or_else(|| format!("grant for unknown user {}", name))?;
+                user.grants.push(grant);
+                Ok(())
+            }
+            _ => Err(format!("unrecognised line: {}", line)),
+        }
+    }
+
+    /// Checks the password. It takes as long for a user that doesn't exist
+    /// as for one that does, so the time taken doesn't give away which
+    /// users exist. Argon2 is slow on purpose, so this shouldn't be called
+    /// from an async task.
+    pub fn verify(&self, user: &str, password: &str) -> bool {
+        let (hash, known) = match self.users.get(user) {
+            Some(user) => (user.password_hash.as_str(), true),
+            None => (dummy_hash(), false),
+        };
+        // Already validated by Acl::parse
+        let hash = PasswordHash::new(hash).unwrap();
+        let verified = Argon2::default().verify_password(password.as_bytes(), &hash).is_ok();
+        verified && known
+    }
+
+    pub fn is_allowed(&self, user: &str, command: Command, key: &str) -> bool {
+        self.users.get(user).is_some_and(|user| {
+            user.grants
+                .iter()
+                .any(|grant| grant.commands.contains(&command) && grant.keys.matches(key))
+        })
+    }
+}
+
+impl KeyPattern {
+    fn parse(input: &str) -> KeyPattern {
+        if input == "*" {
+            KeyPattern::Any
+        } else if let Some(prefix) = input.strip_suffix('*') {
+            KeyPattern::Prefix(prefix.to_string())
+        } else {
+            KeyPattern::Exact(input.to_string())
+        }
+    }
+
+    fn matches(&self, key: &str) -> bool {
+        match *self {
+            KeyPattern::Any => true,
+            KeyPattern::Prefix(ref prefix) => key.starts_with(prefix.as_str()),
+            KeyPattern::Exact(ref exact) => key == exact,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use argon2::{Algorithm, Params, Version};
+
+    /// Hashes with the cheapest Argon2 parameters so the tests stay fast,
+    /// verification reads the parameters back out of the hash
+    pub fn cheap_hash(password: &str) -> String {
+        let params = Params::new(8, 1, 1, None).unwrap();
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let salt = SaltString::generate(&mut OsRng);
+        argon2.hash_password(password.as_bytes(), &salt).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_hash_password_round_trips() {
+        let config = format!("user u {}\n", hash_password("secret").unwrap());
+        let acl = Acl::parse(&config).unwrap();
+
+        assert!(acl.verify("u", "secret"));
+        assert!(!acl.verify("u", "Secret"));
+        assert!(!acl.verify("v", "secret"));
+        // Unknown users are checked against a dummy hash, which mustn't let
+        // its own password in
+        assert!(!acl.verify("v", "no such user"));
+    }
+
+    #[test]
+    fn test_key_pattern_parse() {
+        assert_eq!(KeyPattern::parse("*"), KeyPattern::Any);
+        assert_eq!(KeyPattern::parse("metrics:*"), KeyPattern::Prefix("metrics:".into()));
+        assert_eq!(KeyPattern::parse("foo"), KeyPattern::Exact("foo".into()));
+    }
+
+    #[test]
+    fn test_acl_parse_errors() {
+        let hash = cheap_hash("pw");
+
+        let err = |config: &str| Acl::parse(config).err().unwrap();
+        assert_eq!(err("grant ghost GET *"), "config line 1: grant for unknown user ghost");
+        assert_eq!(
+            err(&format!("user a {}\ngrant a GET,DEL *", hash)),
+            "config line 2: unknown command: DEL"
+        );
+        assert_eq!(
+            err(&format!("user a {}\n\nuser a {}", hash, hash)),
+            "config line 3: user a is defined twice"
+        );
+        assert_eq!(err("permit a GET *"), "config line 1: unrecognised line: permit a GET *");
+        assert!(err("user a not-a-hash").starts_with("config line 1: invalid password hash for a"));
+    }
+}
@@ -0,0 +1,774 @@
+//! A tiny key/value store spoken to over a line based protocol
+
+pub mod acl;
+pub mod records;
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::task;
+use self::{
+    acl::Acl,
+    records::{read_records, write_records, Format, Record},
+};
+
+pub struct Database {
+    state: Mutex<State>,
+}
+
+struct State {
+    map: HashMap<String, String>,
+    /// Append-only JSON Lines log of every write, `None` when in memory only
+    log: Option<File>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    Auth { user: String, password: String },
+    Get { key: String },
+    Set { key: String, value: String },
+    SetNx { key: String, value: String },
+    Dump { prefix: String },
+}
+
+/// The commands a user can be granted, `AUTH` is always allowed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Get,
+    Set,
+}
+
+pub enum Response {
+    Authenticated {
+        user: String,
+    },
+    Value {
+        key: String,
+        value: String,
+    },
+    Set {
+        key: String,
+        value: String,
+        previous: Option<String>,
+    },
+    Exists {
+        key: String,
+        value: String,
+    },
+    Dump {
+        records: Vec<Record>,
+    },
+    Error {
+        msg: String,
+    }
+}
+
+/// Per-connection state
+pub struct Session {
+    /// `None` when the server is running without authentication
+    acl: Option<Arc<Acl>>,
+    user: Option<String>,
+}
+
+pub async fn handle_request(line: &str, db: &Database, session: &mut Session) -> Response {
+    let request = match Request::parse(line) {
+        Ok(req) => req,
+        Err(e) => return Response::Error { msg: e },
+    };
+
+    if let Request::Auth { user, password } = request {
+        return session.authenticate(user, password).await;
+    }
+
+    if let Err(msg) = session.check_permission(&request) {
+        return Response::Error { msg };
+    }
+
+    let result = match request {
+        Request::Auth { .. } => unreachable!("AUTH is handled before the permission check"),
+        Request::Get { key } => match db.get(&key) {
+            Some(value) => Ok(Response::Value { key, value }),
+            None => Ok(Response::Error {
+                msg: format!("no key {}", key),
+            }),
+        }
+        Request::Set { key, value } => db
+            .set(key.clone(), value.clone())
+            .map(|previous| Response::Set { key, value, previous }),
+        Request::SetNx { key, value } => db
+            .set_if_absent(key.clone(), value.clone())
+            .map(|existing| match existing {
+                Some(existing) => Response::Exists { key, value: existing },
+                None => Response::Set { key, value, previous: None },
+            }),
+        Request::Dump { prefix } => {
+            let mut records = db.dump(&prefix);
+            // Only the keys this user could GET one at a time
+            records.retain(|record| session.is_allowed(Command::Get, &record.key));
+            Ok(Response::Dump { records })
+        }
+    };
+
+    result.unwrap_or_else(|e| Response::Error {
+        msg: format!("could not persist write: {}", e),
+    })
+}
+
+impl Database {
+    /// An in memory database
+    pub fn new(map: HashMap<String, String>) -> Database {
+        Database {
+            state: Mutex::new(State { map, log: None }),
+        }
+    }
+
+    /// Opens the persistence log at `path`, creating it if needed.
+    ///
+    /// The log is replayed and then rewritten with one record per key, so it
+    /// only grows by the writes made since the last time it was opened. Only
+    /// one process may have a log open at a time.
+    pub fn open(path: &Path) -> io::Result<Database> {
+        let map = match read_log(path) {
+            Ok(map) => map,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut compacted = path.as_os_str().to_owned();
+        compacted.push(".compacting");
+        let mut file = File::create(&compacted)?;
+        write_records(Format::JsonLines, &mut file, sorted_records(&map, ""))?;
+        file.sync_all()?;
+        fs::rename(&compacted, path)?;
+
+        let log = OpenOptions::new().append(true).open(path)?;
+        Ok(Database {
+            state: Mutex::new(State { map, log: Some(log) }),
+        })
+    }
+
+    /// Reads the persistence log at `path` into an in memory database. The
+    /// file is never written, so the log may be open in a server meanwhile,
+    /// and it must already exist.
+    pub fn load(path: &Path) -> io::Result<Database> {
+        Ok(Database::new(read_log(path)?))
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.state.lock().unwrap().map.get(key).cloned()
+    }
+
+    /// Sets the value and returns the previous one
+    pub fn set(&self, key: String, value: String) -> io::Result<Option<String>> {
+        check_record(&key, &value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut state = self.state.lock().unwrap();
+        state.append_to_log(&key, &value)?;
+        Ok(state.map.insert(key, value))
+    }
+
+    /// Only sets the value when the key isn't already set, otherwise returns
+    /// the existing value
+    pub fn set_if_absent(&self, key: String, value: String) -> io::Result<Option<String>> {
+        check_record(&key, &value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut state = self.state.lock().unwrap();
+        if let Some(existing) = state.map.get(&key) {
+            return Ok(Some(existing.clone()));
+        }
+        state.append_to_log(&key, &value)?;
+        state.map.insert(key, value);
+        Ok(None)
+    }
+
+    /// All the records whose key starts with `prefix`, sorted by key
+    pub fn dump(&self, prefix: &str) -> Vec<Record> {
+        sorted_records(&self.state.lock().unwrap().map, prefix)
+    }
+}
+
+impl State {
+    fn append_to_log(&mut self, key: &str, value: &str) -> io::Result<()> {
+        if let Some(ref mut log) = self.log {
+            let record = Record {
+                key: key.to_string(),
+                value: value.to_string(),
+            };
+            // One write per record so a failed write can't leave half a line
+            // in the middle of the log
+            let mut line = serde_json::to_string(&record)?;
+            line.push('\n');
+            log.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Replays the log at `path`, later records for a key replacing earlier ones
+fn read_log(path: &Path) -> io::Result<HashMap<String, String>> {
+    let mut map = HashMap::new();
+    for result in read_records(Format::JsonLines, File::open(path)?) {
+        let record = result.map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })?;
+        map.insert(record.key, record.value);
+    }
+    Ok(map)
+}
+
+/// Refuses a key or value that couldn't be read back from the log, or
+/// sent back in a response
+fn check_record(key: &str, value: &str) -> Result<(), String> {
+    let record = Record {
+        key: key.to_string(),
+        value: value.to_string(),
+    };
+    record.validate()
+}
+
+/// The next space separated word, an empty word from a doubled or trailing
+/// space counts as missing
+fn next_word<'a>(parts: &mut impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    parts.next().filter(|word| !word.is_empty())
+}
+
+fn sorted_records(map: &HashMap<String, String>, prefix: &str) -> Vec<Record> {
+    let mut records: Vec<Record> = map
+        .iter()
+        .filter(|(key, _)| key.starts_with(prefix))
+        .map(|(key, value)| Record {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect();
+    records.sort_by(|a, b| a.key.cmp(&b.key));
+    records
+}
+
+impl Request {
+    /// Parses one line of the protocol. Words are separated by single spaces
+    /// and the last part, a value or password, is taken as is so it may
+    /// contain spaces of its own or be empty.
+    pub fn parse(input: &str) -> Result<Request, String> {
+        let mut parts = input.splitn(3, ' ');
+        match parts.next() {
+            Some("AUTH") => {
+                let user = next_word(&mut parts).ok_or("AUTH must be followed by a user")?;
+                let password = parts.next().ok_or("AUTH needs a password")?;
+                Ok(Request::Auth {
+                    user: user.to_string(),
+                    password: password.to_string(),
+                })
+            }
+            Some("GET") => {
+                let key = next_word(&mut parts).ok_or("GET must be followed by a key")?;
+                if parts.next().is_some() {
+                    return Err("GET's key must not be followed by anything".into());
+                }
+                Ok(Request::Get {
+                    key: key.to_string(),
+                })
+            }
+            Some("SET") => {
+                let key = next_word(&mut parts).ok_or("SET must be followed by a key")?;
+                let value = parts.next().ok_or("SET needs a value")?;
+                check_record(key, value)?;
+                Ok(Request::Set {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+            }
+            Some("SETNX") => {
+                let key = next_word(&mut parts).ok_or("SETNX must be followed by a key")?;
+                let value = parts.next().ok_or("SETNX needs a value")?;
+                check_record(key, value)?;
+                Ok(Request::SetNx {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+            }
+            Some("DUMP") => {
+                let prefix = parts.next().unwrap_or("");
+                if parts.next().is_some() {
+                    return Err("DUMP's prefix must not be followed by anything".into());
+                }
+                Ok(Request::Dump {
+                    prefix: prefix.to_string(),
+                })
+            }
+            Some("") | None => Err("empty input".into()),
+            Some(cmd) => Err(format!("unknown command: {}", cmd)),
+        }
+    }
+
+    /// The line that parses back to this request
+    pub fn serialize(&self) -> String {
+        match *self {
+            Request::Auth { ref user, ref password } => format!("AUTH {} {}", user, password),
+            Request::Get { ref key } => format!("GET {}", key),
+            Request::Set { ref key, ref value } => format!("SET {} {}", key, value),
+            Request::SetNx { ref key, ref value } => format!("SETNX {} {}", key, value),
+            Request::Dump { ref prefix } if prefix.is_empty() => "DUMP".to_string(),
+            Request::Dump { ref prefix } => format!("DUMP {}", prefix),
+        }
+    }
+
+    /// The command and key this request needs permission for, `None` for
+    /// `AUTH` and for `DUMP` which filters out the keys that aren't allowed
+    fn permission(&self) -> Option<(Command, &str)> {
+        match *self {
+            Request::Auth { .. } | Request::Dump { .. } => None,
+            Request::Get { ref key } => Some((Command::Get, key)),
+            Request::Set { ref key, .. } | Request::SetNx { ref key, .. } => {
+                Some((Command::Set, key))
+            }
+        }
+    }
+}
+
+impl Command {
+    fn parse(input: &str) -> Result<Command, String> {
+        match input {
+            "GET" => Ok(Command::Get),
+            "SET" => Ok(Command::Set),
+            cmd => Err(format!("unknown command: {}", cmd)),
+        }
+    }
+}
+
+impl Response {
+    /// A `DUMP` is sent as one `key = value` line per record followed by an
+    /// `end of dump` line, every other response is a single line
+    pub fn serialize(&self) -> String {
+        match *self {
+            Response::Authenticated { ref user } => format!("authenticated as {}", user),
+            Response::Value { ref key, ref value } => format!("{} = {}", key, value),
+            Response::Set {
+                ref key,
+                ref value,
+                ref previous,
+            } => format!("set {} = {}, previous = {:?}", key, value, previous),
+            Response::Exists { ref key, ref value } => format!("exists {} = {}", key, value),
+            Response::Dump { ref records } => {
+                let mut out = String::new();
+                for record in records {
+                    out.push_str(&format!("{} = {}\n", record.key, record.value));
+                }
+                out.push_str(&format!("end of dump, {} keys", records.len()));
+                out
+            }
+            Response::Error { ref msg } => format!("error: {}", msg),
+        }
+    }
+}
+
+impl Session {
+    pub fn new(acl: Option<Arc<Acl>>) -> Session {
+        Session { acl, user: None }
+    }
+
+    async fn authenticate(&mut self, user: String, password: String) -> Response {
+        let acl = match self.acl {
+            Some(ref acl) => acl.clone(),
+            None => return Response::Error {
+                msg: "authentication is not enabled".into(),
+            },
+        };
+
+        // A failed attempt also drops any identity the connection had before
+        self.user = None;
+        // Hashing takes long enough to hold up the other connections on this
+        // worker, so it runs on the blocking pool
+        let verifying = {
+            let user = user.clone();
+            task::spawn_blocking(move || acl.verify(&user, &password))
+        };
+        if verifying.await.unwrap_or(false) {
+            self.user = Some(user.clone());
+            Response::Authenticated { user }
+        } else {
+            Response::Error {
+                msg: "invalid user or password".into(),
+            }
+        }
+    }
+
+    fn check_permission(&self, request: &Request) -> Result<(), String> {
+        if self.acl.is_some() && self.user.is_none() {
+            return Err("authentication required".into());
+        }
+        match request.permission() {
+            Some((command, key)) if !self.is_allowed(command, key) => {
+                Err(format!("permission denied: {:?} {}", command, key))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_allowed(&self, command: Command, key: &str) -> bool {
+        match (&self.acl, &self.user) {
+            (None, _) => true,
+            (Some(acl), Some(user)) => acl.is_allowed(user, command, key),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::acl::tests::cheap_hash;
+
+    fn acl() -> Arc<Acl> {
+        let config = format!(
+            "# test users\n\
+             user admin {}\n\
+             grant admin GET,SET *\n\
+             \n\
+             user reader {}\n\
+             grant reader GET metrics:*\n\
+             grant reader SET scratch\n\
+             user nobody {}\n",
+            cheap_hash("admin-pw"),
+            cheap_hash("reader-pw"),
+            cheap_hash("nobody-pw"),
+        );
+        Arc::new(Acl::parse(&config).unwrap())
+    }
+
+    fn db() -> Database {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), "bar".to_string());
+        map.insert("metrics:cpu".to_string(), "42".to_string());
+        Database::new(map)
+    }
+
+    fn request(line: &str, db: &Database, session: &mut Session) -> String {
+        let mut runtime = tokio::runtime::Builder::new().basic_scheduler().build().unwrap();
+        runtime.block_on(handle_request(line, db, session)).serialize()
+    }
+
+    #[test]
+    fn test_without_acl_everything_is_allowed() {
+        let db = db();
+        let mut session = Session::new(None);
+
+        assert_eq!(request("GET foo", &db, &mut session), "foo = bar");
+        assert_eq!(
+            request("SET foo baz", &db, &mut session),
+            "set foo = baz, previous = Some(\"bar\")"
+        );
+        assert_eq!(
+            request("AUTH admin admin-pw", &db, &mut session),
+            "error: authentication is not enabled"
+        );
+    }
+
+    #[test]
+    fn test_unauthenticated_connections_may_only_auth() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+
+        assert_eq!(request("GET foo", &db, &mut session), "error: authentication required");
+        assert_eq!(request("SET foo baz", &db, &mut session), "error: authentication required");
+        assert_eq!(db.get("foo").unwrap(), "bar");
+
+        assert_eq!(request("AUTH admin admin-pw", &db, &mut session), "authenticated as admin");
+        assert_eq!(request("GET foo", &db, &mut session), "foo = bar");
+    }
+
+    #[test]
+    fn test_parse_errors_are_reported_before_authentication() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+
+        assert_eq!(request("DEL foo", &db, &mut session), "error: unknown command: DEL");
+        assert_eq!(request("AUTH admin", &db, &mut session), "error: AUTH needs a password");
+    }
+
+    #[test]
+    fn test_auth_rejects_wrong_password_and_unknown_user() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+
+        assert_eq!(
+            request("AUTH admin reader-pw", &db, &mut session),
+            "error: invalid user or password"
+        );
+        assert_eq!(
+            request("AUTH mallory admin-pw", &db, &mut session),
+            "error: invalid user or password"
+        );
+        assert_eq!(request("GET foo", &db, &mut session), "error: authentication required");
+    }
+
+    #[test]
+    fn test_failed_auth_drops_previous_identity() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+
+        assert_eq!(request("AUTH admin admin-pw", &db, &mut session), "authenticated as admin");
+        assert_eq!(request("AUTH admin wrong", &db, &mut session), "error: invalid user or password");
+        assert_eq!(request("GET foo", &db, &mut session), "error: authentication required");
+    }
+
+    #[test]
+    fn test_password_may_contain_spaces() {
+        let config = format!("user spacey {}\ngrant spacey GET *\n", cheap_hash("correct horse battery"));
+        let db = db();
+        let mut session = Session::new(Some(Arc::new(Acl::parse(&config).unwrap())));
+
+        assert_eq!(
+            request("AUTH spacey correct horse battery", &db, &mut session),
+            "authenticated as spacey"
+        );
+    }
+
+    #[test]
+    fn test_prefix_grant_is_read_only() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+        request("AUTH reader reader-pw", &db, &mut session);
+
+        assert_eq!(request("GET metrics:cpu", &db, &mut session), "metrics:cpu = 42");
+        assert_eq!(request("GET metrics:mem", &db, &mut session), "error: no key metrics:mem");
+        assert_eq!(
+            request("SET metrics:cpu 0", &db, &mut session),
+            "error: permission denied: Set metrics:cpu"
+        );
+        assert_eq!(request("GET foo", &db, &mut session), "error: permission denied: Get foo");
+        assert_eq!(db.get("metrics:cpu").unwrap(), "42");
+    }
+
+    #[test]
+    fn test_exact_grant_only_matches_that_key() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+        request("AUTH reader reader-pw", &db, &mut session);
+
+        assert_eq!(
+            request("SET scratch 1", &db, &mut session),
+            "set scratch = 1, previous = None"
+        );
+        assert_eq!(
+            request("SET scratchpad 1", &db, &mut session),
+            "error: permission denied: Set scratchpad"
+        );
+        assert_eq!(request("GET scratch", &db, &mut session), "error: permission denied: Get scratch");
+    }
+
+    #[test]
+    fn test_user_without_grants_can_do_nothing() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+
+        assert_eq!(request("AUTH nobody nobody-pw", &db, &mut session), "authenticated as nobody");
+        assert_eq!(request("GET foo", &db, &mut session), "error: permission denied: Get foo");
+    }
+
+    #[test]
+    fn test_reauthenticating_switches_user() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+
+        request("AUTH reader reader-pw", &db, &mut session);
+        assert_eq!(request("SET foo baz", &db, &mut session), "error: permission denied: Set foo");
+
+        request("AUTH admin admin-pw", &db, &mut session);
+        assert_eq!(
+            request("SET foo baz", &db, &mut session),
+            "set foo = baz, previous = Some(\"bar\")"
+        );
+    }
+
+    #[test]
+    fn test_sessions_are_independent() {
+        let db = db();
+        let acl = acl();
+        let mut admin = Session::new(Some(acl.clone()));
+        let mut anonymous = Session::new(Some(acl));
+
+        request("AUTH admin admin-pw", &db, &mut admin);
+        assert_eq!(request("GET foo", &db, &mut admin), "foo = bar");
+        assert_eq!(request("GET foo", &db, &mut anonymous), "error: authentication required");
+    }
+
+    #[test]
+    fn test_setnx_only_sets_missing_keys() {
+        let db = db();
+        let mut session = Session::new(None);
+
+        assert_eq!(request("SETNX foo baz", &db, &mut session), "exists foo = bar");
+        assert_eq!(request("SETNX new baz", &db, &mut session), "set new = baz, previous = None");
+        assert_eq!(db.get("foo").unwrap(), "bar");
+        assert_eq!(db.get("new").unwrap(), "baz");
+    }
+
+    #[test]
+    fn test_setnx_needs_set_permission() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+        request("AUTH reader reader-pw", &db, &mut session);
+
+        assert_eq!(
+            request("SETNX metrics:new 1", &db, &mut session),
+            "error: permission denied: Set metrics:new"
+        );
+        assert_eq!(
+            request("SETNX scratch 1", &db, &mut session),
+            "set scratch = 1, previous = None"
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let db = db();
+        let mut session = Session::new(None);
+
+        assert_eq!(
+            request("DUMP", &db, &mut session),
+            "foo = bar\nmetrics:cpu = 42\nend of dump, 2 keys"
+        );
+        assert_eq!(
+            request("DUMP metrics:", &db, &mut session),
+            "metrics:cpu = 42\nend of dump, 1 keys"
+        );
+        assert_eq!(request("DUMP nothing", &db, &mut session), "end of dump, 0 keys");
+        assert_eq!(
+            request("DUMP a b", &db, &mut session),
+            "error: DUMP's prefix must not be followed by anything"
+        );
+    }
+
+    #[test]
+    fn test_dump_only_includes_readable_keys() {
+        let db = db();
+        let mut session = Session::new(Some(acl()));
+
+        assert_eq!(request("DUMP", &db, &mut session), "error: authentication required");
+
+        request("AUTH reader reader-pw", &db, &mut session);
+        assert_eq!(
+            request("DUMP", &db, &mut session),
+            "metrics:cpu = 42\nend of dump, 1 keys"
+        );
+
+        request("AUTH nobody nobody-pw", &db, &mut session);
+        assert_eq!(request("DUMP", &db, &mut session), "end of dump, 0 keys");
+    }
+
+    #[test]
+    fn test_parse_edge_cases() {
+        let set = |key: &str, value: &str| Request::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+
+        assert_eq!(Request::parse("SET key "), Ok(set("key", "")));
+        assert_eq!(Request::parse("SET key  spaced  out "), Ok(set("key", " spaced  out ")));
+        assert_eq!(Request::parse("SET key"), Err("SET needs a value".into()));
+        assert_eq!(Request::parse("SET  value"), Err("SET must be followed by a key".into()));
+        assert_eq!(Request::parse("GET "), Err("GET must be followed by a key".into()));
+        assert_eq!(
+            Request::parse("GET foo "),
+            Err("GET's key must not be followed by anything".into())
+        );
+        assert_eq!(Request::parse("AUTH  pw"), Err("AUTH must be followed by a user".into()));
+        assert_eq!(Request::parse("DUMP "), Ok(Request::Dump { prefix: "".into() }));
+        assert_eq!(Request::parse(""), Err("empty input".into()));
+        assert_eq!(Request::parse(" GET foo"), Err("empty input".into()));
+        assert_eq!(Request::parse("get foo"), Err("unknown command: get".into()));
+    }
+
+    #[test]
+    fn test_writes_that_could_not_be_read_back_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.jsonl");
+        let db = Database::open(&path).unwrap();
+        let mut session = Session::new(None);
+
+        assert_eq!(
+            request("SET a\tb v", &db, &mut session),
+            "error: key \"a\\tb\" contains whitespace"
+        );
+        assert_eq!(
+            request("SETNX k one\rtwo", &db, &mut session),
+            "error: value for k contains a newline"
+        );
+        let err = db.set("a\tb".into(), "v".into()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        db.set("ok".into(), "v".into()).unwrap();
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.dump(""), vec![Record { key: "ok".into(), value: "v".into() }]);
+    }
+
+    #[test]
+    fn test_open_persists_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.jsonl");
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.get("foo"), None);
+        db.set("foo".into(), "bar".into()).unwrap();
+        db.set("foo".into(), "baz".into()).unwrap();
+        db.set_if_absent("foo".into(), "ignored".into()).unwrap();
+        db.set_if_absent("new".into(), "value".into()).unwrap();
+        drop(db);
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.get("foo").unwrap(), "baz");
+        assert_eq!(db.get("new").unwrap(), "value");
+    }
+
+    #[test]
+    fn test_open_compacts_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.jsonl");
+
+        let db = Database::open(&path).unwrap();
+        db.set("b".into(), "1".into()).unwrap();
+        db.set("a".into(), "1".into()).unwrap();
+        db.set("b".into(), "2".into()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+        drop(db);
+
+        Database::open(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"key\":\"a\",\"value\":\"1\"}\n{\"key\":\"b\",\"value\":\"2\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_load_only_reads_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.jsonl");
+        let log = "{\"key\":\"b\",\"value\":\"1\"}\n{\"key\":\"b\",\"value\":\"2\"}\n";
+        fs::write(&path, log).unwrap();
+
+        let db = Database::load(&path).unwrap();
+        assert_eq!(db.get("b").unwrap(), "2");
+        db.set("c".into(), "3".into()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), log);
+
+        let missing = dir.path().join("missing.jsonl");
+        let err = Database::load(&missing).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn test_open_rejects_corrupt_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.jsonl");
+        fs::write(&path, "{\"key\":\"a\",\"value\":\"1\"}\n{\"key\":").unwrap();
+
+        let err = Database::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("line 2"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Key/value records in JSON Lines or CSV, used for backups and the
+//! persistence log
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    iter,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// One `{"key": ..., "value": ...}` object per line
+    JsonLines,
+    /// A `key,value` header followed by one record per row
+    Csv,
+}
+
+impl Record {
+    /// Keys are sent unquoted in the line protocol so may not contain
+    /// whitespace, and neither keys nor values may contain newlines
+    pub fn validate(&self) -> Result<(), String> {
+        if self.key.is_empty() {
+            return Err("empty key".into());
+        }
+        if self.key.contains(char::is_whitespace) {
+            return Err(format!("key {:?} contains whitespace", self.key));
+        }
+        if self.value.contains(['\n', '\r']) {
+            return Err(format!("value for {} contains a newline", self.key));
+        }
+        Ok(())
+    }
+}
+
+impl Format {
+    pub fn parse(input: &str) -> Result<Format, String> {
+        match input {
+            "jsonl" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            format => Err(format!("unknown format: {}", format)),
+        }
+    }
+}
+
+/// Writes the records and returns how many were written
+pub fn write_records<W, I>(format: Format, out: W, records: I) -> io::Result<usize>
+where
+    W: Write,
+    I: IntoIterator<Item = Record>,
+{
+    let mut count = 0;
+    match format {
+        Format::JsonLines => {
+            let mut out = io::BufWriter::new(out);
+            for record in records {
+                serde_json::to_writer(&mut out, &record)?;
+                out.write_all(b"\n")?;
+                count += 1;
+            }
+            out.flush()?;
+        }
+        Format::Csv => {
+            let mut out = csv::Writer::from_writer(out);
+            // serialize only writes the header along with the first record
+            out.write_record(["key", "value"])?;
+            for record in records {
+                out.write_record([&record.key, &record.value])?;
+                count += 1;
+            }
+            out.flush()?;
+        }
+    }
+    Ok(count)
+}
+
+/// Reads records one at a time, a bad record is reported with its line
+/// number and doesn't stop the records after it from being read
+pub fn read_records<'a, R>(
+    format: Format,
+    input: R,
+) -> Box<dyn Iterator<Item = Result<Record, String>> + 'a>
+where
+    R: Read + 'a,
+{
+    match format {
+        Format::JsonLines => Box::new(
+            BufReader::new(input)
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+                .map(|(n, line)| {
+                    let line = line.map_err(|e| format!("line {}: {}", n + 1, e))?;
+                    let record: Record = serde_json::from_str(&line)
+                        .map_err(|e| format!("line {}: {}", n + 1, e))?;
+                    record.validate().map_err(|e| format!("line {}: {}", n + 1, e))?;
+                    Ok(record)
+                }),
+        ),
+        Format::Csv => {
+            let mut reader = csv::Reader::from_reader(input);
+            let headers = match reader.headers() {
+                Ok(headers) => headers.clone(),
+                Err(e) => return Box::new(iter::once(Err(csv_error(&e)))),
+            };
+            Box::new(reader.into_records().map(move |row| {
+                let row = row.map_err(|e| csv_error(&e))?;
+                let line = row.position().map_or(0, |pos| pos.line());
+                let record: Record = row
+                    .deserialize(Some(&headers))
+                    .map_err(|e| format!("line {}: {}", line, e))?;
+                record.validate().map_err(|e| format!("line {}: {}", line, e))?;
+                Ok(record)
+            }))
+        }
+    }
+}
+
+fn csv_error(e: &csv::Error) -> String {
+    match e.position() {
+        Some(pos) => format!("line {}: {}", pos.line(), e),
+        None => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(key: &str, value: &str) -> Record {
+        Record {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn round_trip(format: Format) {
+        let records = vec![
+            record("foo", "bar"),
+            record("quoted", "a \"quoted\", value"),
+            record("empty", ""),
+        ];
+
+        let mut out = Vec::new();
+        assert_eq!(write_records(format, &mut out, records.clone()).unwrap(), 3);
+
+        let read: Result<Vec<_>, _> = read_records(format, &out[..]).collect();
+        assert_eq!(read.unwrap(), records);
+    }
+
+    #[test]
+    fn test_json_lines_round_trip() {
+        round_trip(Format::JsonLines);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        round_trip(Format::Csv);
+    }
+
+    #[test]
+    fn test_json_lines_format() {
+        let mut out = Vec::new();
+        write_records(Format::JsonLines, &mut out, vec![record("foo", "bar")]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"key\":\"foo\",\"value\":\"bar\"}\n");
+    }
+
+    #[test]
+    fn test_csv_format() {
+        let mut out = Vec::new();
+        write_records(Format::Csv, &mut out, vec![record("foo", "bar, baz")]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "key,value\nfoo,\"bar, baz\"\n");
+    }
+
+    #[test]
+    fn test_empty_csv_still_has_header() {
+        let mut out = Vec::new();
+        write_records(Format::Csv, &mut out, vec![]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "key,value\n");
+    }
+
+    #[test]
+    fn test_json_lines_bad_records_are_skipped() {
+        let input = "{\"key\":\"a\",\"value\":\"1\"}\n\
+                     not json\n\
+                     \n\
+                     {\"key\":\"has space\",\"value\":\"2\"}\n\
+                     {\"key\":\"b\",\"value\":\"two\\nlines\"}\n\
+                     {\"key\":\"c\",\"value\":\"3\"}\n";
+
+        let results: Vec<_> = read_records(Format::JsonLines, input.as_bytes()).collect();
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0], Ok(record("a", "1")));
+        assert!(results[1].as_ref().unwrap_err().starts_with("line 2: "));
+        assert_eq!(results[2], Err("line 4: key \"has space\" contains whitespace".into()));
+        assert_eq!(results[3], Err("line 5: value for b contains a newline".into()));
+        assert_eq!(results[4], Ok(record("c", "3")));
+    }
+
+    #[test]
+    fn test_csv_bad_records_are_skipped() {
+        let input = "key,value\na,1\nb\nc,3\nhas space,4\nd,\"two\nlines\"\n";
+
+        let results: Vec<_> = read_records(Format::Csv, input.as_bytes()).collect();
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0], Ok(record("a", "1")));
+        assert!(results[1].as_ref().unwrap_err().starts_with("line 3: "));
+        assert_eq!(results[2], Ok(record("c", "3")));
+        assert_eq!(results[3], Err("line 5: key \"has space\" contains whitespace".into()));
+        assert_eq!(results[4], Err("line 6: value for d contains a newline".into()));
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!(Format::parse("jsonl"), Ok(Format::JsonLines));
+        assert_eq!(Format::parse("csv"), Ok(Format::Csv));
+        assert_eq!(Format::parse("xml"), Err("unknown format: xml".into()));
+    }
+}
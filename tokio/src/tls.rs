@@ -0,0 +1,140 @@
+//! TLS termination for the servers, with the certificates loaded from PEM
+//! files
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio_rustls::{
+    rustls::{
+        internal::pemfile, AllowAnyAuthenticatedClient, Certificate, NoClientAuth, PrivateKey,
+        RootCertStore, ServerConfig,
+    },
+    TlsAcceptor,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TlsOptions {
+    /// The server's certificate chain, leaf first
+    pub cert: PathBuf,
+    /// The server's private key, PKCS#8 or RSA
+    pub key: PathBuf,
+    /// When given, clients must present a certificate signed by one of
+    /// these CAs
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsOptions {
+    /// Checks the TLS command line options fit together, `None` if TLS is
+    /// turned off
+    pub fn from_paths(
+        cert: Option<PathBuf>,
+        key: Option<PathBuf>,
+        client_ca: Option<PathBuf>,
+    ) -> Result<Option<TlsOptions>, String> {
+        match (cert, key) {
+            (Some(cert), Some(key)) => Ok(Some(TlsOptions { cert, key, client_ca })),
+            (None, None) if client_ca.is_none() => Ok(None),
+            (None, None) => Err("--tls-client-ca needs --tls-cert and --tls-key".into()),
+            _ => Err("--tls-cert and --tls-key must be given together".into()),
+        }
+    }
+
+    /// Loads the files, failing if any of them is missing or doesn't hold
+    /// what it should
+    pub fn acceptor(&self) -> io::Result<TlsAcceptor> {
+        let verifier = match self.client_ca {
+            Some(ref path) => AllowAnyAuthenticatedClient::new(load_roots(path)?),
+            None => NoClientAuth::new(),
+        };
+
+        let mut config = ServerConfig::new(verifier);
+        config
+            .set_single_cert(load_certs(&self.cert)?, load_key(&self.key)?)
+            .map_err(|e| invalid(&self.key, &e.to_string()))?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+fn load_certs(path: &Path) -> io::Result<Vec<Certificate>> {
+    let certs = pemfile::certs(&mut BufReader::new(File::open(path)?))
+        .map_err(|()| invalid(path, "unreadable certificate"))?;
+    if certs.is_empty() {
+        return Err(invalid(path, "no certificates found"));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> io::Result<PrivateKey> {
+    let mut keys = pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(path)?))
+        .map_err(|()| invalid(path, "unreadable private key"))?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut BufReader::new(File::open(path)?))
+            .map_err(|()| invalid(path, "unreadable private key"))?;
+    }
+    match keys.len() {
+        1 => Ok(keys.remove(0)),
+        0 => Err(invalid(path, "no private key found")),
+        _ => Err(invalid(path, "more than one private key")),
+    }
+}
+
+fn load_roots(path: &Path) -> io::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    let (valid, invalid_count) = roots
+        .add_pem_file(&mut BufReader::new(File::open(path)?))
+        .map_err(|()| invalid(path, "unreadable certificate"))?;
+    if valid == 0 || invalid_count > 0 {
+        return Err(invalid(path, "expected only CA certificates"));
+    }
+    Ok(roots)
+}
+
+fn invalid(path: &Path, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_from_paths() {
+        let path = |p: &str| Some(PathBuf::from(p));
+
+        assert_eq!(TlsOptions::from_paths(None, None, None), Ok(None));
+        assert_eq!(
+            TlsOptions::from_paths(path("c.pem"), path("k.pem"), path("ca.pem")),
+            Ok(Some(TlsOptions {
+                cert: "c.pem".into(),
+                key: "k.pem".into(),
+                client_ca: path("ca.pem"),
+            }))
+        );
+        assert!(TlsOptions::from_paths(path("c.pem"), None, None).is_err());
+        assert!(TlsOptions::from_paths(None, path("k.pem"), None).is_err());
+        assert!(TlsOptions::from_paths(None, None, path("ca.pem")).is_err());
+    }
+
+    #[test]
+    fn test_bad_files_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let not_pem = dir.path().join("not.pem");
+        fs::write(&not_pem, "hello").unwrap();
+        let options = |cert: &Path, key: &Path| TlsOptions {
+            cert: cert.into(),
+            key: key.into(),
+            client_ca: None,
+        };
+
+        let err = options(&dir.path().join("missing.pem"), &not_pem).acceptor().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let err = options(&not_pem, &not_pem).acceptor().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("no certificates found"), "{}", err);
+    }
+}
@@ -0,0 +1,253 @@
+//! A UDP echo server that can simulate an unreliable network by dropping,
+//! duplicating or reordering the datagrams it sends back
+
+use std::{io, net::SocketAddr, time::Duration};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::{net::UdpSocket, time::timeout};
+
+/// Large enough for any UDP datagram
+const MAX_DATAGRAM: usize = 65_536;
+
+/// How long a held back datagram waits for another to overtake it
+const REORDER_WINDOW: Duration = Duration::from_millis(50);
+
+/// Percentages of datagrams to mistreat, from 0 to 100
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Faults {
+    pub drop: f64,
+    pub duplicate: f64,
+    pub reorder: f64,
+    /// The same seed mistreats the same datagrams every time
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Datagram {
+    pub data: Vec<u8>,
+    pub addr: SocketAddr,
+}
+
+/// Decides what happens to each datagram
+pub struct FaultInjector {
+    faults: Faults,
+    rng: StdRng,
+    /// A datagram being held back until the next one has been sent
+    held: Option<Datagram>,
+}
+
+impl FaultInjector {
+    pub fn new(faults: Faults) -> FaultInjector {
+        FaultInjector {
+            rng: StdRng::seed_from_u64(faults.seed),
+            faults,
+            held: None,
+        }
+    }
+
+    /// Takes a received datagram and returns the ones to send now
+    pub fn process(&mut self, datagram: Datagram) -> Vec<Datagram> {
+        // Always roll all three so one fault doesn't shift the others
+        let drop = self.roll(self.faults.drop);
+        let duplicate = self.roll(self.faults.duplicate);
+        let reorder = self.roll(self.faults.reorder);
+
+        if drop {
+            return Vec::new();
+        }
+        let mut out = vec![datagram.clone()];
+        if duplicate {
+            out.push(datagram);
+        }
+        match self.held.take() {
+            Some(held) => out.push(held),
+            None if reorder => {
+                // A duplicate is sent now, so the copies arrive out of order
+                self.held = out.pop();
+            }
+            None => {}
+        }
+        out
+    }
+
+    /// Releases the held back datagram, if there is one
+    pub fn flush(&mut self) -> Option<Datagram> {
+        self.held.take()
+    }
+
+    pub fn is_holding(&self) -> bool {
+        self.held.is_some()
+    }
+
+    fn roll(&mut self, percent: f64) -> bool {
+        self.rng.gen_bool(percent / 100.0)
+    }
+}
+
+/// Echoes datagrams back to their senders until the socket fails. Errors
+/// that only concern one datagram or peer are logged and skipped.
+pub async fn serve(mut socket: UdpSocket, faults: Faults) -> io::Result<()> {
+    let mut injector = FaultInjector::new(faults);
+    let mut buf = vec![0; MAX_DATAGRAM];
+
+    loop {
+        let received = if injector.is_holding() {
+            match timeout(REORDER_WINDOW, socket.recv_from(&mut buf)).await {
+                Ok(received) => received,
+                // Nothing came along to overtake it, send it anyway
+                Err(_) => {
+                    if let Some(held) = injector.flush() {
+                        send(&mut socket, held).await?;
+                    }
+                    continue;
+                }
+            }
+        } else {
+            socket.recv_from(&mut buf).await
+        };
+
+        let (len, addr) = match received {
+            Ok(received) => received,
+            Err(ref e) if is_per_datagram(e) => {
+                eprintln!("error receiving a datagram: {}", e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let datagram = Datagram {
+            data: buf[..len].to_vec(),
+            addr,
+        };
+        for datagram in injector.process(datagram) {
+            send(&mut socket, datagram).await?;
+        }
+    }
+}
+
+/// Only fails if the socket itself has
+async fn send(socket: &mut UdpSocket, datagram: Datagram) -> io::Result<()> {
+    match socket.send_to(&datagram.data, &datagram.addr).await {
+        Ok(_) => Ok(()),
+        Err(ref e) if is_per_datagram(e) => {
+            eprintln!("error sending a datagram to {}: {}", datagram.addr, e);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Errors caused by one peer or datagram, such as an ICMP port unreachable
+/// from an earlier send that's reported by the next receive. The socket
+/// still works for everyone else.
+fn is_per_datagram(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable
+            | io::ErrorKind::AddrNotAvailable
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::Interrupted
+    )
+}
+
+/// Parses a percentage from 0 to 100
+pub fn parse_percent(input: &str) -> Result<f64, String> {
+    input
+        .parse()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+        .ok_or_else(|| format!("invalid percentage: {}", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datagram(n: u8) -> Datagram {
+        Datagram {
+            data: vec![n],
+            addr: "127.0.0.1:9".parse().unwrap(),
+        }
+    }
+
+    /// Runs datagrams 0 to count through an injector and returns the
+    /// numbers sent back, in order
+    fn run(faults: Faults, count: u8) -> Vec<u8> {
+        let mut injector = FaultInjector::new(faults);
+        let mut out: Vec<u8> = (0..count)
+            .flat_map(|n| injector.process(datagram(n)))
+            .map(|datagram| datagram.data[0])
+            .collect();
+        out.extend(injector.flush().map(|datagram| datagram.data[0]));
+        out
+    }
+
+    #[test]
+    fn test_no_faults_echoes_everything_in_order() {
+        assert_eq!(run(Faults::default(), 5), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_always_drop() {
+        let faults = Faults { drop: 100.0, ..Faults::default() };
+        assert_eq!(run(faults, 5), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_always_duplicate() {
+        let faults = Faults { duplicate: 100.0, ..Faults::default() };
+        assert_eq!(run(faults, 3), vec![0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_always_reorder_swaps_pairs() {
+        let faults = Faults { reorder: 100.0, ..Faults::default() };
+        assert_eq!(run(faults, 5), vec![1, 0, 3, 2, 4]);
+    }
+
+    #[test]
+    fn test_duplicates_can_be_reordered() {
+        let faults = Faults {
+            duplicate: 100.0,
+            reorder: 100.0,
+            ..Faults::default()
+        };
+        assert_eq!(run(faults, 2), vec![0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_same_seed_same_faults() {
+        let faults = Faults {
+            drop: 20.0,
+            duplicate: 20.0,
+            reorder: 20.0,
+            seed: 7,
+        };
+        let first = run(faults.clone(), 200);
+
+        assert_eq!(run(faults.clone(), 200), first);
+        assert_ne!(run(Faults { seed: 8, ..faults }, 200), first);
+    }
+
+    #[test]
+    fn test_drop_rate_is_roughly_right() {
+        let faults = Faults { drop: 10.0, seed: 1, ..Faults::default() };
+        let mut injector = FaultInjector::new(faults);
+        let sent: usize = (0..10_000).map(|_| injector.process(datagram(0)).len()).sum();
+
+        assert!((8_800..=9_200).contains(&sent), "sent {}", sent);
+    }
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("0"), Ok(0.0));
+        assert_eq!(parse_percent("12.5"), Ok(12.5));
+        assert_eq!(parse_percent("100"), Ok(100.0));
+        assert!(parse_percent("101").is_err());
+        assert!(parse_percent("-1").is_err());
+        assert!(parse_percent("NaN").is_err());
+        assert!(parse_percent("lots").is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::{SinkExt, StreamExt};
+use hello_world::chat::Chat;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tokio_util::codec::{Framed, LinesCodec};
+
+struct Client {
+    lines: Framed<TcpStream, LinesCodec>,
+}
+
+/// A connection that sends `input` and then fails a read, as if it had
+/// been reset, after which nothing more arrives. Writes to it succeed.
+struct Broken {
+    input: &'static [u8],
+    failed: bool,
+}
+
+impl Broken {
+    fn new(input: &'static [u8]) -> Broken {
+        Broken { input, failed: false }
+    }
+}
+
+/// Starts a chat server on a free port
+async fn start(capacity: usize) -> SocketAddr {
+    serve(Arc::new(Chat::new(capacity))).await
+}
+
+/// Serves `chat` on a free port
+async fn serve(chat: Arc<Chat>) -> SocketAddr {
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            // Otherwise delayed ACKs make the large messages crawl
+            socket.set_nodelay(true).unwrap();
+            let chat = chat.clone();
+            tokio::spawn(async move { chat.handle_connection(socket).await });
+        }
+    });
+
+    addr
+}
+
+impl AsyncRead for Broken {
+    fn poll_read(mut self: Pin<&mut Self>, _: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if self.failed {
+            return Poll::Pending;
+        }
+        if self.input.is_empty() {
+            self.failed = true;
+            return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+        }
+        let len = buf.len().min(self.input.len());
+        buf[..len].copy_from_slice(&self.input[..len]);
+        self.input = &self.input[len..];
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl AsyncWrite for Broken {
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Client {
+    async fn connect(addr: SocketAddr) -> Client {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        stream.set_nodelay(true).unwrap();
+        let mut client = Client {
+            lines: Framed::new(stream, LinesCodec::new()),
+        };
+        assert_eq!(client.recv().await, "* welcome, pick a nickname");
+        client
+    }
+
+    async fn join(addr: SocketAddr, nick: &str) -> Client {
+        let mut client = Client::connect(addr).await;
+        client.send(nick).await;
+        assert_eq!(client.recv().await, format!("* you are {}, in lobby", nick));
+        client
+    }
+
+    async fn send(&mut self, line: &str) {
+        self.lines.send(line.to_string()).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> String {
+        let line = timeout(Duration::from_secs(5), self.lines.next())
+            .await
+            .expect("timed out waiting for a line");
+        line.expect("connection closed").unwrap()
+    }
+
+    async fn assert_silent(&mut self) {
+        if let Ok(line) = timeout(Duration::from_millis(100), self.lines.next()).await {
+            panic!("expected nothing, got {:?}", line);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_lines_are_broadcast_to_everyone_else() {
+    let addr = start(16).await;
+    let mut alice = Client::join(addr, "alice").await;
+    let mut bob = Client::join(addr, "bob").await;
+    assert_eq!(alice.recv().await, "* bob joined lobby");
+    let mut carol = Client::join(addr, "carol").await;
+    assert_eq!(alice.recv().await, "* carol joined lobby");
+    assert_eq!(bob.recv().await, "* carol joined lobby");
+
+    alice.send("hello everyone").await;
+
+    assert_eq!(bob.recv().await, "alice: hello everyone");
+    assert_eq!(carol.recv().await, "alice: hello everyone");
+    alice.assert_silent().await;
+}
+
+#[tokio::test]
+async fn test_rooms_are_separate() {
+    let addr = start(16).await;
+    let mut alice = Client::join(addr, "alice").await;
+    let mut bob = Client::join(addr, "bob").await;
+    assert_eq!(alice.recv().await, "* bob joined lobby");
+
+    bob.send("/join games").await;
+    assert_eq!(bob.recv().await, "* you joined games");
+    assert_eq!(alice.recv().await, "* bob left lobby");
+
+    alice.send("anyone there?").await;
+    bob.send("just me").await;
+    alice.assert_silent().await;
+    bob.assert_silent().await;
+
+    alice.send("/join games").await;
+    assert_eq!(alice.recv().await, "* you joined games");
+    assert_eq!(bob.recv().await, "* alice joined games");
+    alice.send("found you").await;
+    assert_eq!(bob.recv().await, "alice: found you");
+}
+
+#[tokio::test]
+async fn test_who_lists_the_current_room() {
+    let addr = start(16).await;
+    let alice = Client::join(addr, "alice").await;
+    let mut bob = Client::join(addr, "bob").await;
+    let mut carol = Client::join(addr, "carol").await;
+    carol.send("/join elsewhere").await;
+    assert_eq!(carol.recv().await, "* you joined elsewhere");
+
+    bob.send("/who").await;
+    assert_eq!(bob.recv().await, "* carol joined lobby");
+    assert_eq!(bob.recv().await, "* carol left lobby");
+    assert_eq!(bob.recv().await, "* in lobby: alice, bob");
+
+    carol.send("/who").await;
+    assert_eq!(carol.recv().await, "* in elsewhere: carol");
+
+    drop(alice);
+}
+
+#[tokio::test]
+async fn test_nick_renames() {
+    let addr = start(16).await;
+    let mut alice = Client::join(addr, "alice").await;
+    let mut bob = Client::join(addr, "bob").await;
+    assert_eq!(alice.recv().await, "* bob joined lobby");
+
+    bob.send("/nick alice").await;
+    assert_eq!(bob.recv().await, "* nickname alice is taken");
+
+    bob.send("/nick robert").await;
+    assert_eq!(bob.recv().await, "* you are now robert");
+    assert_eq!(alice.recv().await, "* bob is now robert");
+
+    bob.send("hi").await;
+    assert_eq!(alice.recv().await, "robert: hi");
+
+    alice.send("/who").await;
+    assert_eq!(alice.recv().await, "* in lobby: alice, robert");
+
+    // The old nickname is free again
+    let mut bob = Client::join(addr, "bob").await;
+    bob.assert_silent().await;
+}
+
+#[tokio::test]
+async fn test_nickname_is_checked_when_joining() {
+    let addr = start(16).await;
+    let _alice = Client::join(addr, "alice").await;
+    let mut client = Client::connect(addr).await;
+
+    client.send("alice").await;
+    assert_eq!(client.recv().await, "* nickname alice is taken");
+    client.send("al ice").await;
+    assert_eq!(client.recv().await, "* nicknames may not contain spaces");
+    client.send("/alice").await;
+    assert_eq!(client.recv().await, "* nicknames may not start with / or *");
+    client.send("").await;
+    assert_eq!(client.recv().await, "* nicknames must be 1 to 32 characters");
+    client.send("alice2").await;
+    assert_eq!(client.recv().await, "* you are alice2, in lobby");
+}
+
+#[tokio::test]
+async fn test_disconnecting_leaves_the_room() {
+    let addr = start(16).await;
+    let mut alice = Client::join(addr, "alice").await;
+    let bob = Client::join(addr, "bob").await;
+    assert_eq!(alice.recv().await, "* bob joined lobby");
+
+    drop(bob);
+    assert_eq!(alice.recv().await, "* bob left lobby");
+
+    let _bob = Client::join(addr, "bob").await;
+    assert_eq!(alice.recv().await, "* bob joined lobby");
+}
+
+#[tokio::test]
+async fn test_a_broken_connection_leaves_the_room() {
+    let chat = Arc::new(Chat::new(16));
+    let mut alice = Client::join(serve(chat.clone()).await, "alice").await;
+
+    // Before picking a nickname
+    let broken = chat.handle_connection(Broken::new(b""));
+    timeout(Duration::from_secs(5), broken).await.expect("still connected");
+
+    let broken = chat.handle_connection(Broken::new(b"carol\n"));
+    timeout(Duration::from_secs(5), broken).await.expect("still connected");
+    assert_eq!(alice.recv().await, "* carol joined lobby");
+    assert_eq!(alice.recv().await, "* carol left lobby");
+    alice.assert_silent().await;
+}
+
+#[tokio::test]
+async fn test_bad_commands() {
+    let addr = start(16).await;
+    let mut alice = Client::join(addr, "alice").await;
+
+    alice.send("/shout hi").await;
+    assert_eq!(alice.recv().await, "* unknown command /shout");
+    alice.send("/join").await;
+    assert_eq!(alice.recv().await, "* usage: /join <room>");
+    alice.send("/nick a b").await;
+    assert_eq!(alice.recv().await, "* usage: /nick <name>");
+    alice.send("/who cares").await;
+    assert_eq!(alice.recv().await, "* usage: /who");
+}
+
+#[tokio::test]
+async fn test_lagging_client_does_not_block_the_others() {
+    const MESSAGES: usize = 300;
+    let addr = start(4).await;
+    let mut alice = Client::join(addr, "alice").await;
+    let mut sloth = Client::join(addr, "sloth").await;
+    let mut bob = Client::join(addr, "bob").await;
+    assert_eq!(alice.recv().await, "* sloth joined lobby");
+    assert_eq!(alice.recv().await, "* bob joined lobby");
+    assert_eq!(sloth.recv().await, "* bob joined lobby");
+
+    // Much more than the socket buffers hold, so the server soon can't write
+    // any more to sloth, who isn't reading. Alice keeps up with bob.
+    let padding = "x".repeat(60_000);
+    for n in 0..MESSAGES {
+        bob.send(&format!("{} {}", n, padding)).await;
+        let line = alice.recv().await;
+        assert!(line.starts_with(&format!("bob: {} x", n)), "message {}: {:.40}", n, line);
+    }
+
+    let mut received = 0;
+    loop {
+        let line = sloth.recv().await;
+        if line.starts_with("* missed ") {
+            break;
+        }
+        assert!(line.starts_with("bob: "));
+        received += 1;
+    }
+    assert!(received < MESSAGES);
+}
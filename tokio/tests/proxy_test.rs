@@ -0,0 +1,160 @@
+use std::{net::SocketAddr, time::Duration};
+use hello_world::{
+    listen::ListenAddr,
+    proxy::{self, Config, Counters},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::oneshot,
+    time::{timeout, Instant},
+};
+
+/// Starts an echo server on a free port
+async fn start_upstream() -> SocketAddr {
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = socket.split();
+                tokio::io::copy(&mut reader, &mut writer).await.unwrap();
+            });
+        }
+    });
+
+    addr
+}
+
+/// Starts a proxy for one connection, which reports its byte counts
+async fn start_proxy(config: Config) -> (SocketAddr, oneshot::Receiver<Counters>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (sender, receiver) = oneshot::channel();
+
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (counters, result) = proxy::proxy(socket, &config, 1).await;
+        result.unwrap();
+        sender.send(counters).unwrap();
+    });
+
+    (addr, receiver)
+}
+
+fn config(upstream: SocketAddr) -> Config {
+    Config {
+        upstream: ListenAddr::Tcp(upstream.to_string()),
+        log: None,
+        latency: Duration::from_millis(0),
+        bandwidth: None,
+    }
+}
+
+/// Sends `data` through the proxy, closes the sending side and reads the
+/// echo back until the proxy closes too
+async fn round_trip(addr: SocketAddr, data: &[u8]) -> Vec<u8> {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let (mut reader, mut writer) = stream.split();
+
+    let write = async {
+        writer.write_all(data).await.unwrap();
+        writer.shutdown().await.unwrap();
+    };
+    let read = async {
+        let mut echoed = Vec::new();
+        reader.read_to_end(&mut echoed).await.unwrap();
+        echoed
+    };
+    let ((), echoed) = timeout(Duration::from_secs(10), futures::future::join(write, read))
+        .await
+        .expect("timed out");
+    echoed
+}
+
+#[tokio::test]
+async fn test_forwards_both_ways_and_counts_bytes() {
+    let (addr, counters) = start_proxy(config(start_upstream().await)).await;
+    // More than fits in the socket buffers, so both directions must be
+    // copied at the same time
+    let data: Vec<u8> = (0..4_000_000u32).map(|n| n as u8).collect();
+
+    assert!(round_trip(addr, &data).await == data);
+
+    let counters = counters.await.unwrap();
+    assert_eq!(counters, Counters { to_upstream: 4_000_000, to_client: 4_000_000 });
+}
+
+#[tokio::test]
+async fn test_logging_does_not_change_the_data() {
+    let mut config = config(start_upstream().await);
+    config.log = Some(proxy::LogMode::Lines);
+    let (addr, counters) = start_proxy(config).await;
+
+    assert_eq!(round_trip(addr, b"one\ntwo\nthree").await, b"one\ntwo\nthree");
+    assert_eq!(counters.await.unwrap(), Counters { to_upstream: 13, to_client: 13 });
+}
+
+#[tokio::test]
+async fn test_latency_is_added_each_way() {
+    let mut config = config(start_upstream().await);
+    config.latency = Duration::from_millis(100);
+    let (addr, _) = start_proxy(config).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    let start = Instant::now();
+    stream.write_all(b"ping").await.unwrap();
+    let mut buf = [0; 4];
+    stream.read_exact(&mut buf).await.unwrap();
+    let elapsed = start.elapsed();
+
+    assert_eq!(&buf, b"ping");
+    assert!(elapsed >= Duration::from_millis(200), "took {:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1000), "took {:?}", elapsed);
+}
+
+#[tokio::test]
+async fn test_latency_does_not_limit_throughput() {
+    let mut config = config(start_upstream().await);
+    config.latency = Duration::from_millis(50);
+    let (addr, _) = start_proxy(config).await;
+    let data = vec![7; 1_000_000];
+
+    // Chunks are delayed, not sent one per round trip
+    let start = Instant::now();
+    assert_eq!(round_trip(addr, &data).await.len(), data.len());
+    assert!(start.elapsed() < Duration::from_secs(3), "took {:?}", start.elapsed());
+}
+
+#[tokio::test]
+async fn test_bandwidth_is_throttled() {
+    let mut config = config(start_upstream().await);
+    config.bandwidth = Some(100_000);
+    let (addr, counters) = start_proxy(config).await;
+    let data = vec![7; 30_000];
+
+    // Each direction is throttled separately but they overlap, so 30KB at
+    // 100KB/s takes at least 0.3s
+    let start = Instant::now();
+    assert_eq!(round_trip(addr, &data).await, data);
+    let elapsed = start.elapsed();
+
+    assert!(elapsed >= Duration::from_millis(250), "took {:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "took {:?}", elapsed);
+    assert_eq!(counters.await.unwrap(), Counters { to_upstream: 30_000, to_client: 30_000 });
+}
+
+#[tokio::test]
+async fn test_unreachable_upstream() {
+    // Bind and drop to find a port nobody is listening on
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    let (socket, _) = listener.accept().await.unwrap();
+
+    let (counters, result) = proxy::proxy(socket, &config(upstream), 1).await;
+    assert!(result.is_err());
+    assert_eq!(counters, Counters::default());
+}
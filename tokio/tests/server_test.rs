@@ -0,0 +1,179 @@
+use std::{net::SocketAddr, time::Duration};
+use hello_world::{
+    listen::{self, Connection, ListenAddr, Listener},
+    server::{Handler, Server},
+};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::timeout,
+};
+
+/// Reports each connection's id as it starts, then keeps the connection
+/// open until the client closes it
+fn holding_handler(started: mpsc::UnboundedSender<u64>) -> impl Handler {
+    move |mut socket: Box<dyn Connection>, id| {
+        let started = started.clone();
+        async move {
+            started.send(id).unwrap();
+            let mut buf = Vec::new();
+            let _ = socket.read_to_end(&mut buf).await;
+        }
+    }
+}
+
+struct Running {
+    addr: SocketAddr,
+    started: mpsc::UnboundedReceiver<u64>,
+    shutdown: oneshot::Sender<()>,
+    /// How many connections were still open when it finished
+    finished: JoinHandle<usize>,
+}
+
+/// Runs a server on a free port until told to shut down
+async fn start(configure: impl FnOnce(Server) -> Server) -> Running {
+    start_with(Vec::new(), configure).await
+}
+
+/// Runs a server on `others` as well as a free port
+async fn start_with(mut others: Vec<Listener>, configure: impl FnOnce(Server) -> Server) -> Running {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (started_sender, started) = mpsc::unbounded_channel();
+    let (shutdown, shutdown_receiver) = oneshot::channel::<()>();
+
+    others.push(Listener::Tcp(listener));
+    let server = configure(Server::new(others));
+    let finished = tokio::spawn(server.run(holding_handler(started_sender), async {
+        let _ = shutdown_receiver.await;
+    }));
+
+    Running {
+        addr,
+        started,
+        shutdown,
+        finished,
+    }
+}
+
+impl Running {
+    async fn next_started(&mut self) -> u64 {
+        timeout(Duration::from_secs(5), self.started.recv())
+            .await
+            .expect("timed out waiting for a connection")
+            .unwrap()
+    }
+
+    async fn assert_none_started(&mut self) {
+        if let Ok(id) = timeout(Duration::from_millis(200), self.started.recv()).await {
+            panic!("expected no new connection, got {:?}", id);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_connections_are_numbered() {
+    let mut server = start(|server| server).await;
+
+    let mut clients = Vec::new();
+    for id in 1..=3 {
+        clients.push(TcpStream::connect(server.addr).await.unwrap());
+        assert_eq!(server.next_started().await, id);
+    }
+}
+
+#[tokio::test]
+async fn test_serves_every_listener() {
+    let dir = tempfile::tempdir().unwrap();
+    let unix_addr = ListenAddr::Unix(dir.path().join("test.sock"));
+    let tcp_addr = ListenAddr::Tcp("127.0.0.1:0".into());
+    let server = Server::bind(&[unix_addr.clone(), tcp_addr], None).await.unwrap();
+    let (started_sender, mut started) = mpsc::unbounded_channel();
+    tokio::spawn(server.run(holding_handler(started_sender), futures::future::pending()));
+
+    let _client = listen::connect(&unix_addr).await.unwrap();
+    assert_eq!(started.recv().await, Some(1));
+}
+
+#[tokio::test]
+async fn test_connection_limit() {
+    let mut server = start(|server| server.max_connections(2)).await;
+
+    let first = TcpStream::connect(server.addr).await.unwrap();
+    let _second = TcpStream::connect(server.addr).await.unwrap();
+    // Connects, but waits in the backlog
+    let _third = TcpStream::connect(server.addr).await.unwrap();
+    assert_eq!(server.next_started().await, 1);
+    assert_eq!(server.next_started().await, 2);
+    server.assert_none_started().await;
+
+    drop(first);
+    assert_eq!(server.next_started().await, 3);
+}
+
+#[tokio::test]
+async fn test_the_connection_over_the_limit_waits_for_a_slot() {
+    let mut server = start(|server| server.max_connections(1)).await;
+
+    let first = TcpStream::connect(server.addr).await.unwrap();
+    assert_eq!(server.next_started().await, 1);
+    // Its connection is up and stays open, but nothing serves it yet
+    let mut extra = TcpStream::connect(server.addr).await.unwrap();
+    server.assert_none_started().await;
+    let mut buf = [0; 1];
+    assert!(timeout(Duration::from_millis(100), extra.read(&mut buf)).await.is_err());
+
+    drop(first);
+    assert_eq!(server.next_started().await, 2);
+}
+
+#[tokio::test]
+async fn test_connection_limit_is_shared_by_every_listener() {
+    let dir = tempfile::tempdir().unwrap();
+    let unix_addr = ListenAddr::Unix(dir.path().join("test.sock"));
+    let unix_listener = Listener::bind(&unix_addr, None).await.unwrap();
+    let mut server = start_with(vec![unix_listener], |server| server.max_connections(1)).await;
+
+    // The idle Unix listener mustn't keep the only permit to itself
+    let tcp_client = TcpStream::connect(server.addr).await.unwrap();
+    assert_eq!(server.next_started().await, 1);
+    let _unix_client = listen::connect(&unix_addr).await.unwrap();
+    server.assert_none_started().await;
+
+    drop(tcp_client);
+    assert_eq!(server.next_started().await, 2);
+}
+
+#[tokio::test]
+async fn test_shutdown_waits_for_open_connections() {
+    let mut server = start(|server| server.grace_period(Duration::from_secs(5))).await;
+    let client = TcpStream::connect(server.addr).await.unwrap();
+    assert_eq!(server.next_started().await, 1);
+
+    server.shutdown.send(()).unwrap();
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+
+    // No longer accepting, but the open connection carries on
+    assert!(TcpStream::connect(server.addr).await.is_err());
+    let mut finished = server.finished;
+    assert!(timeout(Duration::from_millis(100), &mut finished).await.is_err());
+
+    drop(client);
+    let open = timeout(Duration::from_secs(5), finished).await.unwrap().unwrap();
+    assert_eq!(open, 0);
+}
+
+#[tokio::test]
+async fn test_shutdown_gives_up_after_the_grace_period() {
+    let mut server = start(|server| server.grace_period(Duration::from_millis(100))).await;
+    let _client = TcpStream::connect(server.addr).await.unwrap();
+    let _other = TcpStream::connect(server.addr).await.unwrap();
+    server.next_started().await;
+    server.next_started().await;
+
+    server.shutdown.send(()).unwrap();
+    let open = timeout(Duration::from_secs(5), server.finished).await.unwrap().unwrap();
+    assert_eq!(open, 2);
+}
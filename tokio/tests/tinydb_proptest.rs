@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use futures::executor::block_on;
+use hello_world::tinydb::{handle_request, Database, Request, Session};
+use proptest::prelude::*;
+
+/// Keys and users are single words
+fn word() -> impl Strategy<Value = String> {
+    "[^ \n]{1,16}"
+}
+
+/// Values and passwords are the rest of the line, spaces included
+fn rest_of_line() -> impl Strategy<Value = String> {
+    "[^\n]{0,32}"
+}
+
+/// Keys that are written must also read back from the log, so they can't
+/// have any whitespace, nor values a carriage return
+fn record() -> impl Strategy<Value = (String, String)> {
+    ("[^\\s]{1,16}", "[^\r\n]{0,32}")
+}
+
+fn request() -> impl Strategy<Value = Request> {
+    prop_oneof![
+        (word(), rest_of_line()).prop_map(|(user, password)| Request::Auth { user, password }),
+        word().prop_map(|key| Request::Get { key }),
+        record().prop_map(|(key, value)| Request::Set { key, value }),
+        record().prop_map(|(key, value)| Request::SetNx { key, value }),
+        "[^ \n]{0,8}".prop_map(|prefix| Request::Dump { prefix }),
+    ]
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Get(String),
+    Set(String, String),
+    SetNx(String, String),
+    Dump(String),
+}
+
+/// A small key space so the operations keep running into each other
+fn op() -> impl Strategy<Value = Op> {
+    let key = "[ab]{1,2}";
+    let value = "[xy ]{0,3}";
+    prop_oneof![
+        key.prop_map(Op::Get),
+        (key, value).prop_map(|(key, value)| Op::Set(key, value)),
+        (key, value).prop_map(|(key, value)| Op::SetNx(key, value)),
+        "[ab]{0,1}".prop_map(Op::Dump),
+    ]
+}
+
+impl Op {
+    fn line(&self) -> String {
+        match *self {
+            Op::Get(ref key) => format!("GET {}", key),
+            Op::Set(ref key, ref value) => format!("SET {} {}", key, value),
+            Op::SetNx(ref key, ref value) => format!("SETNX {} {}", key, value),
+            Op::Dump(ref prefix) => format!("DUMP {}", prefix),
+        }
+    }
+
+    /// The response the reference model expects, applying the op to it
+    fn apply(&self, model: &mut HashMap<String, String>) -> String {
+        match *self {
+            Op::Get(ref key) => match model.get(key) {
+                Some(value) => format!("{} = {}", key, value),
+                None => format!("error: no key {}", key),
+            },
+            Op::Set(ref key, ref value) => {
+                let previous = model.insert(key.clone(), value.clone());
+                format!("set {} = {}, previous = {:?}", key, value, previous)
+            }
+            Op::SetNx(ref key, ref value) => match model.get(key) {
+                Some(existing) => format!("exists {} = {}", key, existing),
+                None => {
+                    model.insert(key.clone(), value.clone());
+                    format!("set {} = {}, previous = None", key, value)
+                }
+            },
+            Op::Dump(ref prefix) => {
+                let mut keys: Vec<_> = model.keys().filter(|key| key.starts_with(prefix.as_str())).collect();
+                keys.sort();
+                let mut out = String::new();
+                for key in &keys {
+                    out.push_str(&format!("{} = {}\n", key, model[*key]));
+                }
+                out.push_str(&format!("end of dump, {} keys", keys.len()));
+                out
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn serialized_requests_parse_back(request in request()) {
+        prop_assert_eq!(Request::parse(&request.serialize()), Ok(request));
+    }
+
+    #[test]
+    fn parse_accepts_its_own_serialization(line in "\\PC*") {
+        if let Ok(request) = Request::parse(&line) {
+            prop_assert_eq!(Request::parse(&request.serialize()), Ok(request));
+        }
+    }
+
+    #[test]
+    fn parse_never_panics_on_protocol_like_input(line in "(AUTH|GET|SET|SETNX|DUMP)?[ a-z]{0,12}") {
+        let _ = Request::parse(&line);
+    }
+
+    #[test]
+    fn database_behaves_like_a_hash_map(ops in prop::collection::vec(op(), 1..50)) {
+        let db = Database::new(HashMap::new());
+        let mut session = Session::new(None);
+        let mut model = HashMap::new();
+
+        for op in &ops {
+            let expected = op.apply(&mut model);
+            // Without authentication nothing needs a runtime
+            let actual = block_on(handle_request(&op.line(), &db, &mut session)).serialize();
+            prop_assert_eq!(actual, expected, "after {:?}", op);
+        }
+
+        for record in db.dump("") {
+            prop_assert_eq!(model.remove(&record.key), Some(record.value));
+        }
+        prop_assert!(model.is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    fs,
+    io::BufReader,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use hello_world::{
+    listen::{Connection, Listener},
+    server::Server,
+    tls::TlsOptions,
+};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tokio_rustls::{
+    rustls::{internal::pemfile, ClientConfig},
+    TlsConnector,
+};
+
+/// A certificate authority made up for one test, and a directory to keep
+/// the PEM files it signs
+struct Pki {
+    dir: TempDir,
+    ca: Certificate,
+}
+
+impl Pki {
+    fn new(name: &str) -> Pki {
+        let mut params = CertificateParams::default();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, name);
+        let pki = Pki {
+            dir: tempfile::tempdir().unwrap(),
+            ca: Certificate::from_params(params).unwrap(),
+        };
+        fs::write(pki.path("ca.pem"), pki.ca.serialize_pem().unwrap()).unwrap();
+        pki
+    }
+
+    fn path(&self, file: &str) -> PathBuf {
+        self.dir.path().join(file)
+    }
+
+    /// Signs a new certificate for `name`, writing `<name>.pem` and
+    /// `<name>-key.pem`
+    fn issue(&self, name: &str) -> (PathBuf, PathBuf) {
+        let mut params = CertificateParams::new(vec![name.to_string()]);
+        params.distinguished_name.push(DnType::CommonName, name);
+        let cert = Certificate::from_params(params).unwrap();
+
+        let cert_path = self.path(&format!("{}.pem", name));
+        let key_path = self.path(&format!("{}-key.pem", name));
+        fs::write(&cert_path, cert.serialize_pem_with_signer(&self.ca).unwrap()).unwrap();
+        fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    /// Starts a TLS echo server on a free port with a certificate for
+    /// `localhost`
+    async fn start_server(&self, client_ca: Option<PathBuf>) -> SocketAddr {
+        let (cert, key) = self.issue("localhost");
+        let acceptor = TlsOptions { cert, key, client_ca }.acceptor().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = Server::new(vec![Listener::Tcp(listener)]).tls(acceptor);
+        let handler = |socket: Box<dyn Connection>, _id| async move {
+            let (mut reader, mut writer) = tokio::io::split(socket);
+            let _ = tokio::io::copy(&mut reader, &mut writer).await;
+        };
+        tokio::spawn(server.run(handler, futures::future::pending()));
+        addr
+    }
+
+    /// A client that trusts this CA, presenting `client_cert` if given
+    fn connector(&self, client_cert: Option<(PathBuf, PathBuf)>) -> TlsConnector {
+        connector(&self.path("ca.pem"), client_cert)
+    }
+}
+
+fn connector(ca: &Path, client_cert: Option<(PathBuf, PathBuf)>) -> TlsConnector {
+    let open = |path: &Path| BufReader::new(fs::File::open(path).unwrap());
+    let mut config = ClientConfig::new();
+    config.root_store.add_pem_file(&mut open(ca)).unwrap();
+
+    if let Some((cert, key)) = client_cert {
+        let cert = pemfile::certs(&mut open(&cert)).unwrap();
+        let mut key = pemfile::pkcs8_private_keys(&mut open(&key)).unwrap();
+        config.set_single_client_cert(cert, key.remove(0)).unwrap();
+    }
+    TlsConnector::from(Arc::new(config))
+}
+
+/// Connects, sends a line and returns what was echoed back. With TLS 1.3 a
+/// rejected client certificate only shows up once the client reads.
+async fn echo(connector: &TlsConnector, addr: SocketAddr) -> Result<Vec<u8>, std::io::Error> {
+    let attempt = async {
+        let stream = TcpStream::connect(addr).await?;
+        let name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+        let mut stream = connector.connect(name, stream).await?;
+        stream.write_all(b"hello\n").await?;
+        let mut buf = vec![0; 6];
+        stream.read_exact(&mut buf).await?;
+        Ok(buf)
+    };
+    timeout(Duration::from_secs(5), attempt).await.expect("timed out")
+}
+
+#[tokio::test]
+async fn test_echo_over_tls() {
+    let pki = Pki::new("test ca");
+    let addr = pki.start_server(None).await;
+
+    assert_eq!(echo(&pki.connector(None), addr).await.unwrap(), b"hello\n");
+}
+
+#[tokio::test]
+async fn test_client_rejects_untrusted_server() {
+    let pki = Pki::new("test ca");
+    let addr = pki.start_server(None).await;
+    let other = Pki::new("other ca");
+
+    assert!(echo(&other.connector(None), addr).await.is_err());
+}
+
+#[tokio::test]
+async fn test_plain_tcp_client_gets_no_echo() {
+    let pki = Pki::new("test ca");
+    let addr = pki.start_server(None).await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"hello\n").await.unwrap();
+    let mut buf = Vec::new();
+    let _ = timeout(Duration::from_secs(5), stream.read_to_end(&mut buf)).await.unwrap();
+    assert!(!buf.starts_with(b"hello"));
+}
+
+#[tokio::test]
+async fn test_client_certificate_is_required() {
+    let pki = Pki::new("test ca");
+    let addr = pki.start_server(Some(pki.path("ca.pem"))).await;
+
+    assert!(echo(&pki.connector(None), addr).await.is_err());
+
+    let client = pki.issue("client");
+    assert_eq!(echo(&pki.connector(Some(client)), addr).await.unwrap(), b"hello\n");
+}
+
+#[tokio::test]
+async fn test_client_certificate_from_another_ca_is_rejected() {
+    let pki = Pki::new("test ca");
+    let addr = pki.start_server(Some(pki.path("ca.pem"))).await;
+    let other = Pki::new("other ca");
+
+    // Trusts the server, but its own certificate comes from the wrong CA
+    let connector = connector(&pki.path("ca.pem"), Some(other.issue("stranger")));
+
+    assert!(echo(&connector, addr).await.is_err());
+}
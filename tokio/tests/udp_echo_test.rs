@@ -0,0 +1,105 @@
+use std::{net::SocketAddr, time::Duration};
+use hello_world::udp::{self, Faults};
+use tokio::{
+    net::UdpSocket,
+    time::{delay_for, timeout},
+};
+
+/// Starts a UDP echo server on a free port
+async fn start(faults: Faults) -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(udp::serve(socket, faults));
+    addr
+}
+
+/// Sends datagrams 0 to count one at a time, then collects whatever comes
+/// back until the server goes quiet
+async fn exchange(addr: SocketAddr, count: u16) -> Vec<u16> {
+    let mut socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(addr).await.unwrap();
+
+    for n in 0..count {
+        socket.send(&n.to_be_bytes()).await.unwrap();
+        // Lets the server keep up, so the loopback never drops anything
+        delay_for(Duration::from_millis(1)).await;
+    }
+
+    let mut received = Vec::new();
+    let mut buf = [0; 16];
+    while let Ok(len) = timeout(Duration::from_millis(300), socket.recv(&mut buf)).await {
+        assert_eq!(len.unwrap(), 2);
+        received.push(u16::from_be_bytes([buf[0], buf[1]]));
+    }
+    received
+}
+
+#[tokio::test]
+async fn test_echoes_datagrams() {
+    let addr = start(Faults::default()).await;
+    let mut socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    socket.send_to(b"hello", &addr).await.unwrap();
+    let mut buf = [0; 16];
+    let (len, from) = timeout(Duration::from_secs(5), socket.recv_from(&mut buf))
+        .await
+        .expect("timed out")
+        .unwrap();
+
+    assert_eq!(&buf[..len], b"hello");
+    assert_eq!(from, addr);
+}
+
+#[tokio::test]
+async fn test_faults_are_reproducible() {
+    let faults = Faults {
+        drop: 10.0,
+        duplicate: 10.0,
+        reorder: 10.0,
+        seed: 42,
+    };
+    let first = exchange(start(faults.clone()).await, 200).await;
+    let second = exchange(start(faults).await, 200).await;
+
+    assert_eq!(first, second);
+    // Every kind of fault happened
+    assert!(first.len() != 200);
+    assert!((0..200).any(|n| !first.contains(&n)));
+    assert!(first.windows(2).any(|pair| pair[0] == pair[1]));
+    assert!(first.windows(2).any(|pair| pair[0] > pair[1]));
+}
+
+#[tokio::test]
+async fn test_held_datagram_is_sent_when_nothing_overtakes_it() {
+    let faults = Faults { reorder: 100.0, ..Faults::default() };
+
+    assert_eq!(exchange(start(faults).await, 1).await, vec![0]);
+}
+
+#[tokio::test]
+async fn test_a_closed_peer_does_not_stop_the_server() {
+    let faults = Faults { reorder: 100.0, ..Faults::default() };
+    let mut peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let peer_addr = peer.local_addr().unwrap();
+    // Connected, so the port unreachable for the echo is reported back
+    let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    server.connect(peer_addr).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    let serving = tokio::spawn(udp::serve(server, faults));
+
+    // The echo is held back, and the peer has gone by the time it's sent
+    peer.send_to(b"one", &addr).await.unwrap();
+    drop(peer);
+    delay_for(Duration::from_millis(200)).await;
+
+    let mut peer = UdpSocket::bind(peer_addr).await.unwrap();
+    peer.send_to(b"two", &addr).await.unwrap();
+    let mut buf = [0; 16];
+    let (len, _) = timeout(Duration::from_secs(5), peer.recv_from(&mut buf))
+        .await
+        .expect("timed out")
+        .unwrap();
+
+    assert_eq!(&buf[..len], b"two");
+    assert!(timeout(Duration::from_millis(10), serving).await.is_err());
+}